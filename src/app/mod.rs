@@ -3,162 +3,329 @@ use std::{
     time::{Duration, Instant},
 };
 
-use crate::{bot, game, pentominoes, ui};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::{bot, game, pieces, ui};
 
 const DEFAULT_DELAY_MS: u16 = 350;
 const EMA_ALPHA: f64 = 0.5;
+// placements ranked in the side panel, see `bot::SearchBot::with_ranking()`
+pub const TOP_PLACEMENTS: usize = 5;
 
 pub struct App {
     pub game_state: game::State,
     lookahead_size: u8,
-    pentomino_permutations: pentominoes::Permutations,
+    game_config: game::GameConfig,
+    pieces: pieces::PieceSet,
+    bot: Box<dyn bot::Bot>,
+    // totals over the games played by `play_game()`
+    search_stats: bot::SearchStats,
     id_manager: game::IdManager,
     next_up: game::NextShapes,
+    // gaps of the garbage rows, see `game::garbage`
+    garbage_rng: StdRng,
     last_frame_instance: Option<Instant>,
     pub delay_ms: u16,
     current_frame: Option<game::GameField>,
     frame_buffer: VecDeque<game::GameField>,
     ema_solution_time: Option<Duration>,
     pub is_bot_paused: bool,
+    // alternatives to the last placement, and the one previewed on the board
+    ranking: Vec<bot::RankedPlacement>,
+    hovered_placement: Option<usize>,
 }
 
 impl App {
-    pub fn new(lookahead_size: u8) -> Self {
+    pub fn new(
+        lookahead_size: u8,
+        game_config: game::GameConfig,
+        pieces: pieces::PieceSet,
+        bot: Box<dyn bot::Bot>,
+        randomizer: Box<dyn game::Randomizer>,
+        seed: u64,
+    ) -> Self {
         Self {
-            game_state: game::State::new(lookahead_size),
+            game_state: game::State::new(lookahead_size, game_config),
             lookahead_size,
-            pentomino_permutations: pentominoes::load_permutations(),
+            game_config,
             id_manager: game::IdManager::default(),
-            next_up: game::NextShapes::new(lookahead_size),
+            next_up: game::NextShapes::new(lookahead_size, randomizer, seed),
+            // its own stream, so the gaps don't depend on how many pieces were drawn
+            garbage_rng: StdRng::seed_from_u64(seed.wrapping_add(1)),
+            pieces,
+            bot,
+            search_stats: bot::SearchStats::default(),
             last_frame_instance: None,
             delay_ms: DEFAULT_DELAY_MS,
             current_frame: None,
             frame_buffer: VecDeque::default(),
             ema_solution_time: None,
             is_bot_paused: false,
+            ranking: Vec::new(),
+            hovered_placement: None,
         }
     }
 
-    fn bot_search(&mut self) -> Option<game::GameField> {
-        self.game_state.remaining_pieces = self.next_up.get_next_stack();
+    fn bot_search(&mut self) {
+        self.game_state.remaining_pieces = self.next_up.get_next_stack(&self.game_state);
+        self.game_state.possible_next_pieces = self.next_up.possible_next_shapes();
 
-        match bot::search(
+        self.game_state = play_turn(
             self.game_state.clone(),
-            &self.pentomino_permutations,
+            &self.pieces,
             &mut self.id_manager,
-            &self.lookahead_size,
-        ) {
-            Some(solution) => {
-                let solution_field = solution.field.clone();
-                self.game_state = solution;
-                return Some(solution_field);
+            self.bot.as_mut(),
+            &mut self.search_stats,
+            &mut self.next_up,
+        );
+
+        if self.game_state.status == game::GameStatus::GameOver {
+            println!("GAME OVER");
+            self.ranking.clear();
+        } else {
+            // of the search that chose the placement just played
+            self.ranking = self.bot.ranking().to_vec();
+        }
+    }
+
+    fn restart(&mut self) {
+        self.game_state = game::State::new(self.lookahead_size, self.game_config);
+        self.frame_buffer.clear();
+        self.current_frame = Some(self.game_state.field.clone());
+        self.ema_solution_time = None;
+        self.ranking.clear();
+        self.hovered_placement = None;
+    }
+
+    // plays a game from scratch until game over or `n_searches` pieces are placed,
+    // returns its final state and the time spent searching
+    pub fn play_game(&mut self, n_searches: u32, lookahead_size: u8) -> (game::State, Duration) {
+        let mut state = game::State::new(lookahead_size, self.game_config);
+        let mut solution_time = Duration::new(0, 0);
+
+        for _ in 0..n_searches {
+            state.remaining_pieces = self.next_up.get_next_stack(&state);
+            state.possible_next_pieces = self.next_up.possible_next_shapes();
+
+            let solution_start = Instant::now();
+
+            state = play_turn(
+                state,
+                &self.pieces,
+                &mut self.id_manager,
+                self.bot.as_mut(),
+                &mut self.search_stats,
+                &mut self.next_up,
+            );
+
+            if state.status == game::GameStatus::GameOver {
+                break;
             }
-            None => {
-                println!("NO SOLUTION");
-                return None;
+
+            solution_time += Instant::now() - solution_start;
+
+            game::update(&mut state, &mut self.id_manager);
+            game::garbage::push_if_due(&mut state, &mut self.garbage_rng, &mut self.id_manager);
+
+            if state.status == game::GameStatus::GameOver {
+                break;
             }
-        };
+        }
+
+        (state, solution_time)
     }
 
     pub fn perf_test_bot(&mut self, n_runs: u32, n_searches: u32, lookahead_size: u8) {
         let mut total_run_time = Duration::new(0, 0);
         let mut total_solution_time = Duration::new(0, 0);
-        let mut failed_counter = 0;
+        let mut total_pieces_placed = 0;
+        let mut total_score = 0;
+        let mut game_over_counter = 0;
+        let mut min_pieces_placed = u32::MAX;
+        let mut max_pieces_placed = 0;
 
-        for i in 0..n_runs {
-            let mut state = game::State::new(lookahead_size);
+        self.search_stats = bot::SearchStats::default();
 
+        for i in 0..n_runs {
             let mut run_time = Duration::new(0, 0);
             let run_start = Instant::now();
 
-            let mut failed = false;
-            for _ in 0..n_searches {
-                state.remaining_pieces = self.next_up.get_next_stack();
-
-                let solution_start = Instant::now();
-
-                match bot::search(
-                    state,
-                    &self.pentomino_permutations,
-                    &mut self.id_manager,
-                    &lookahead_size,
-                ) {
-                    Some(solution) => {
-                        let solution_end = Instant::now();
-
-                        total_solution_time += solution_end - solution_start;
-
-                        state = solution;
-                    }
-                    None => {
-                        failed_counter += 1;
-                        failed = true;
-                        break;
-                    }
-                };
-
-                game::update(&mut state, &mut self.id_manager, 0, true);
-            }
+            let (state, solution_time) = self.play_game(n_searches, lookahead_size);
+            total_solution_time += solution_time;
 
             let run_end = Instant::now();
 
             run_time += run_end - run_start;
             total_run_time += run_time;
-
-            if failed {
-                println!("run {}: failed", i + 1);
+            total_pieces_placed += state.pieces_placed;
+            total_score += state.score;
+            min_pieces_placed = min_pieces_placed.min(state.pieces_placed);
+            max_pieces_placed = max_pieces_placed.max(state.pieces_placed);
+
+            if state.status == game::GameStatus::GameOver {
+                game_over_counter += 1;
+                println!(
+                    "run {}: game over after {} pieces, score {}, {:?}",
+                    i + 1,
+                    state.pieces_placed,
+                    state.score,
+                    run_time
+                );
                 continue;
             }
 
-            println!("run {}: {:?}", (i + 1), run_time);
+            println!("run {}: score {}, {:?}", (i + 1), state.score, run_time);
         }
 
-        let total_solutions = (n_runs * n_searches) - failed_counter;
-
-        println!("\ntotal solutions count: {:?}", total_solutions);
+        println!("\ntotal solutions count: {:?}", total_pieces_placed);
         println!(
             "avg solution time: {:?}",
-            total_solution_time / total_solutions
+            total_solution_time / total_pieces_placed.max(1)
         );
         println!(
             "solutions per second: {:.2}",
-            total_solutions as f64 / total_run_time.as_secs_f64()
+            total_pieces_placed as f64 / total_run_time.as_secs_f64()
         );
 
         println!("\navg run time: {:?}", total_run_time / n_runs);
-        println!("failed runs count: {:?}", failed_counter);
+        println!(
+            "avg pieces placed: {:.2}",
+            total_pieces_placed as f64 / n_runs as f64
+        );
+        println!("avg score: {:.2}", total_score as f64 / n_runs as f64);
+        println!("game over runs count: {:?}", game_over_counter);
+
+        if self.game_config.garbage_interval > 0 {
+            println!(
+                "\nsurvival: a garbage row every {} pieces",
+                self.game_config.garbage_interval
+            );
+            println!(
+                "runs survived: {} of {}",
+                n_runs - game_over_counter,
+                n_runs
+            );
+            println!(
+                "pieces survived: avg {:.2}, min {}, max {}",
+                total_pieces_placed as f64 / n_runs as f64,
+                min_pieces_placed,
+                max_pieces_placed
+            );
+        }
+
+        println!("bot: {}", self.bot.name());
+        println!("randomizer: {}", self.next_up.randomizer_name());
+        println!("seed: {}", self.next_up.seed());
+
+        // only collected with `--stats`
+        if self.search_stats.searches > 0 {
+            println!("\nper search:\n{}", self.search_stats);
+        }
     }
 }
 
+// places the first of `state.remaining_pieces`, or the piece holding it swaps in,
+// or ends the game if it cannot spawn
+fn play_turn(
+    state: game::State,
+    pieces: &pieces::PieceSet,
+    id_manager: &mut game::IdManager,
+    bot: &mut dyn bot::Bot,
+    search_stats: &mut bot::SearchStats,
+    next_up: &mut game::NextShapes,
+) -> game::State {
+    if game::is_blocked_out(&state, pieces) {
+        return game::State {
+            status: game::GameStatus::GameOver,
+            ..state
+        };
+    }
+
+    let current_piece = state.remaining_pieces[0];
+
+    let mut placement = bot.choose(&state, id_manager);
+    if let Some(stats) = bot.stats() {
+        search_stats.merge(stats);
+    }
+
+    // every line of the lookahead tops out, the current piece still has to go somewhere
+    if placement.is_none() {
+        let only_current = game::State {
+            remaining_pieces: vec![current_piece],
+            ..state.clone()
+        };
+
+        placement = bot.choose(&only_current, id_manager);
+        if let Some(stats) = bot.stats() {
+            search_stats.merge(stats);
+        }
+    }
+
+    let placement = placement.expect("a piece that can spawn always lands");
+
+    // whatever the bot, its placement is held to the rules
+    let mut state = state;
+    let was_holding = state.held_piece.is_some();
+
+    if let Err(err) = game::apply_placement(&mut state, &placement, pieces, id_manager) {
+        panic!(
+            "{} chose an illegal placement ({}): {}",
+            bot.name(),
+            placement,
+            err
+        );
+    }
+
+    // holding with nothing held brought in the next piece as well
+    if placement.hold && !was_holding {
+        next_up.skip();
+    }
+
+    state
+}
+
 impl eframe::App for App {
     // absolute mess, but i could not manage to make it cleaner
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // `frame_buffer` is empty when game starts or all frames have been rendered
-        if self.frame_buffer.is_empty() {
+        // nothing left to search for once the game is over
+        if self.frame_buffer.is_empty() && self.game_state.status == game::GameStatus::Playing {
             let start_time = Instant::now();
             self.bot_search();
-            let new_solution_time = Some(Instant::now().duration_since(start_time));
+            let new_solution_time = Instant::now().duration_since(start_time);
 
             // felt like some smoothing of solution time was needed, hence employing
             // exponential moving average
             self.ema_solution_time = Some(Duration::from_secs_f64(
-                new_solution_time.unwrap_or_default().as_secs_f64() * EMA_ALPHA
+                new_solution_time.as_secs_f64() * EMA_ALPHA
                     + self.ema_solution_time.unwrap_or_default().as_secs_f64() * (1.0 - EMA_ALPHA),
             ));
 
+            let mut passes = Vec::new();
+
             game::animate_update(
                 &mut self.game_state.field,
+                self.game_config.gravity,
                 &mut self.id_manager,
                 // flag to control recursion
                 true,
-                // start with zero cleared rows for this specific update
-                0,
-                // buffer to update with cleared rows count
-                &mut self.game_state.cleared_rows,
+                // buffer to add rows cleared on each pass to
+                &mut passes,
                 // buffer to add frames to
                 &mut self.frame_buffer,
             );
 
+            game::scoring::record_clears(&mut self.game_state, &passes);
+
+            if game::garbage::push_if_due(
+                &mut self.game_state,
+                &mut self.garbage_rng,
+                &mut self.id_manager,
+            ) {
+                self.frame_buffer.push_back(self.game_state.field.clone());
+            }
+
             // remove duplicate frames, seems expensive
             // but we dont care about performance in the animated version
             let mut seen = HashSet::new();
@@ -176,7 +343,8 @@ impl eframe::App for App {
         {
             self.last_frame_instance = Some(Instant::now());
 
-            if !self.is_bot_paused {
+            // the last frame stays up once the game is over
+            if !self.is_bot_paused && !self.frame_buffer.is_empty() {
                 self.current_frame = self.frame_buffer.pop_front();
             }
         }
@@ -185,17 +353,31 @@ impl eframe::App for App {
             let ema_solution_time_ms =
                 self.ema_solution_time.unwrap_or_default().as_secs_f64() * 1000.0;
 
+            let mut restart_requested = false;
+
             egui::CentralPanel::default().show(ctx, |ui| {
                 // TODO reduce coupling
+                let bot_panel = ui::BotPanel {
+                    ema_solution_time_ms,
+                    search_stats: self.bot.stats(),
+                    ranking: &self.ranking,
+                    hovered_placement: &mut self.hovered_placement,
+                };
+
                 ui::draw_ui(
                     ui,
-                    &frame_to_draw,
+                    frame_to_draw,
                     &mut self.delay_ms,
-                    self.game_state.cleared_rows,
-                    ema_solution_time_ms,
+                    &self.game_state,
+                    bot_panel,
                     &mut self.is_bot_paused,
+                    &mut restart_requested,
                 );
             });
+
+            if restart_requested {
+                self.restart();
+            }
         }
 
         // always repaint to avoid flickering/empty screen
@@ -0,0 +1,121 @@
+use crate::{game, pieces};
+
+use super::{generate_states, heuristic, SearchConfig};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use std::sync::Arc;
+
+// Bastet-style randomizer: hands out the piece whose best placement,
+// as judged by the bot's own heuristic, is the worst
+// pieces are judged against the state they will be placed on, once the pieces drawn
+// before them are placed, see `Randomizer::next_shape()`
+pub struct Adversary {
+    pieces: pieces::PieceSet,
+    // the bot's, only its weights are used
+    config: SearchConfig,
+    id_manager: game::IdManager,
+}
+
+impl Adversary {
+    pub fn new(pieces: pieces::PieceSet, config: SearchConfig) -> Self {
+        Adversary {
+            pieces,
+            config,
+            id_manager: game::IdManager::default(),
+        }
+    }
+
+    // the board once `state.remaining_pieces` are placed, each where the heuristic likes
+    // it best, None if they already are or one of them cannot be placed
+    // greedy rather than the bot's search, which stops short of the whole preview under
+    // a budget or with `Strategy::Mcts`, and would cost a search per draw
+    pub(super) fn project(&mut self, state: &game::State) -> Option<game::State> {
+        if state.remaining_pieces.is_empty() {
+            return None;
+        }
+
+        let mut current = state.clone();
+
+        while let Some(&piece_id) = current.remaining_pieces.first() {
+            let parent = Arc::new(current);
+
+            current = generate_states(&parent, piece_id, &self.pieces, &mut self.id_manager, false)
+                .into_iter()
+                .map(|mut child| {
+                    let value =
+                        heuristic::apply(&mut child, &mut self.id_manager, &self.config.weights);
+                    (value, child)
+                })
+                .max_by_key(|(value, _)| *value)?
+                .1;
+        }
+
+        Some(current)
+    }
+
+    // heuristic of the best reachable placement of `piece_id`, None if it cannot be placed
+    fn best_placement(&mut self, state: &game::State, piece_id: u8) -> Option<i32> {
+        let arc_state = Arc::new(game::State {
+            parent_state: None,
+            placement: None,
+            field: state.field.clone(),
+            remaining_pieces: vec![piece_id],
+            possible_next_pieces: Vec::new(),
+            held_piece: None,
+            cleared_rows: state.cleared_rows,
+            score: state.score,
+            combo: state.combo,
+            pieces_placed: state.pieces_placed,
+            status: state.status,
+            config: state.config,
+        });
+
+        generate_states(
+            &arc_state,
+            piece_id,
+            &self.pieces,
+            &mut self.id_manager,
+            false,
+        )
+        .iter_mut()
+        .map(|child| heuristic::apply(child, &mut self.id_manager, &self.config.weights))
+        .max()
+    }
+}
+
+impl game::Randomizer for Adversary {
+    fn name(&self) -> String {
+        String::from("adversarial")
+    }
+
+    fn next_shape(&mut self, rng: &mut StdRng, state: &game::State) -> u8 {
+        let projected = self.project(state);
+        let state = projected.as_ref().unwrap_or(state);
+
+        let mut worst_shapes = Vec::new();
+        let mut worst_score = i32::MAX;
+
+        for piece_id in 0..self.pieces.len() as u8 {
+            // a piece that cannot be placed at all ends the game, nothing is worse
+            let score = self.best_placement(state, piece_id).unwrap_or(i32::MIN);
+
+            if score < worst_score {
+                worst_score = score;
+                worst_shapes.clear();
+            }
+
+            if score == worst_score {
+                worst_shapes.push(piece_id);
+            }
+        }
+
+        // ties are broken randomly, so the sequence still depends on the seed
+        *worst_shapes.choose(rng).unwrap()
+    }
+
+    // the adversary picks among every piece
+    fn possible_shapes(&self) -> Vec<u8> {
+        (0..self.pieces.len() as u8).collect()
+    }
+}
@@ -0,0 +1,80 @@
+use crate::bot::config::Meter;
+use crate::bot::transposition::TranspositionTable;
+use crate::bot::{expand, expectimax, SearchConfig, SearchStats};
+use crate::{game, pieces};
+
+use std::sync::Arc;
+
+// keeps the `width` best states of each depth, so the work per depth is bounded
+// by `width` times the placements of a single piece, whatever the lookahead
+// once the budget runs out, the best of the deepest depth reached is answered
+pub fn search(
+    first_generation: Vec<(i32, game::State)>,
+    pieces: &pieces::PieceSet,
+    id_manager: &mut game::IdManager,
+    width: usize,
+    config: &SearchConfig,
+    stats: &mut SearchStats,
+) -> Option<(i32, Arc<game::State>)> {
+    let mut meter = Meter::new(config.budget);
+    let mut transpositions = TranspositionTable::new();
+
+    let mut beam: Vec<(i32, Arc<game::State>)> = Vec::new();
+    let mut child_states = first_generation;
+
+    loop {
+        // positions reached before at least as well, e.g. by placing the same pieces in
+        // another order, are dropped, see `State::zobrist()`
+        let generated = child_states.len();
+        let mut children: Vec<(i32, Arc<game::State>)> = child_states
+            .into_iter()
+            .filter(|(heuristic, child)| {
+                let depth = child.remaining_pieces.len() as u8;
+                transpositions.improve(child.zobrist(), *heuristic, depth)
+            })
+            .map(|(heuristic, child)| (heuristic, Arc::new(child)))
+            .collect();
+
+        stats.record_transposition_hits(generated - children.len());
+        stats.record_frontier(children.len());
+
+        // every state of this depth is blocked out, or there was no budget left to find out
+        if children.is_empty() {
+            return match meter.is_exhausted() {
+                true => beam.into_iter().next(),
+                false => None,
+            };
+        }
+
+        // stable, so ties keep generation order like the priority queue does
+        children.sort_by(|(a, _), (b, _)| b.cmp(a));
+        children.truncate(width);
+
+        // the final beam is ranked again, by what can come after the preview
+        if config.expectimax && children[0].1.remaining_pieces.is_empty() {
+            for (heuristic, state) in children.iter_mut() {
+                *heuristic = expectimax::expected_value(state, pieces, id_manager, config)
+                    .unwrap_or(*heuristic);
+            }
+
+            children.sort_by(|(a, _), (b, _)| b.cmp(a));
+        }
+
+        beam = children;
+
+        if beam[0].1.remaining_pieces.is_empty() || meter.is_exhausted() {
+            return beam.into_iter().next();
+        }
+
+        child_states = Vec::new();
+
+        for (_, state) in &beam {
+            if meter.is_exhausted() {
+                break;
+            }
+            meter.spend();
+
+            child_states.extend(expand(state, pieces, id_manager, false, config, stats));
+        }
+    }
+}
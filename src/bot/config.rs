@@ -0,0 +1,154 @@
+use crate::bot::Weights;
+
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_BEAM_WIDTH: usize = 32;
+pub const DEFAULT_MCTS_ITERATIONS: u32 = 500;
+
+// when a search stops and settles for its best answer so far, in states expanded
+// (iterations for `Strategy::Mcts`) or in time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Budget {
+    Iterations(u32),
+    Time(Duration),
+}
+
+// parses "<iterations>" or "<milliseconds>ms"
+impl FromStr for Budget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let budget = match s.strip_suffix("ms") {
+            Some(ms) => ms
+                .parse()
+                .ok()
+                .map(|ms| Budget::Time(Duration::from_millis(ms))),
+            None => s.parse().ok().map(Budget::Iterations),
+        };
+
+        match budget {
+            Some(Budget::Iterations(0)) | None => Err(format!(
+                "invalid budget '{}', expected <iterations> or <milliseconds>ms",
+                s
+            )),
+            Some(budget) => Ok(budget),
+        }
+    }
+}
+
+// tracks what a search has spent against an optional budget
+pub struct Meter {
+    budget: Option<Budget>,
+    start: Instant,
+    spent: u32,
+}
+
+impl Meter {
+    pub fn new(budget: Option<Budget>) -> Self {
+        Meter {
+            budget,
+            start: Instant::now(),
+            spent: 0,
+        }
+    }
+
+    pub fn spend(&mut self) {
+        self.spent += 1;
+    }
+
+    // never before the first step, so there is always an answer
+    pub fn is_exhausted(&self) -> bool {
+        self.spent > 0
+            && match self.budget {
+                Some(Budget::Iterations(iterations)) => self.spent >= iterations,
+                Some(Budget::Time(time)) => self.start.elapsed() >= time,
+                None => false,
+            }
+    }
+}
+
+impl std::fmt::Display for Budget {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Budget::Iterations(iterations) => write!(f, "{}", iterations),
+            Budget::Time(time) => write!(f, "{}ms", time.as_millis()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strategy {
+    #[default]
+    // expands the most promising state first, over all depths,
+    // until a state with no remaining pieces comes out of the queue
+    BestFirst,
+    // expands depth by depth, keeping only the `width` best states of each depth
+    Beam {
+        width: usize,
+    },
+    // Monte Carlo tree search over the placements, until the budget runs out
+    Mcts {
+        budget: Budget,
+    },
+}
+
+// parses "best-first", "beam", "beam:<width>", "mcts" or "mcts:<budget>"
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, parameter) = match s.split_once(':') {
+            Some((name, parameter)) => (name, Some(parameter)),
+            None => (s, None),
+        };
+
+        match (name, parameter) {
+            ("best-first", None) => Ok(Strategy::BestFirst),
+            ("beam", None) => Ok(Strategy::Beam {
+                width: DEFAULT_BEAM_WIDTH,
+            }),
+            ("beam", Some(width)) => match width.parse() {
+                Ok(width) if width > 0 => Ok(Strategy::Beam { width }),
+                _ => Err(format!("invalid beam width '{}'", width)),
+            },
+            ("mcts", None) => Ok(Strategy::Mcts {
+                budget: Budget::Iterations(DEFAULT_MCTS_ITERATIONS),
+            }),
+            ("mcts", Some(budget)) => Ok(Strategy::Mcts {
+                budget: budget.parse()?,
+            }),
+            _ => Err(format!(
+                "invalid search strategy '{}', expected best-first, beam[:<width>] or mcts[:<budget>]",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Strategy::BestFirst => write!(f, "best-first"),
+            Strategy::Beam { width } => write!(f, "beam:{}", width),
+            Strategy::Mcts { budget } => write!(f, "mcts:{}", budget),
+        }
+    }
+}
+
+// how the bot searches, carried alongside the lookahead size
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchConfig {
+    pub strategy: Strategy,
+    // fully looked ahead states are valued by their average over the pieces
+    // that can come after the preview, see `expectimax`
+    pub expectimax: bool,
+    // None searches until done, `Strategy::Mcts` stops at whichever budget runs out first
+    pub budget: Option<Budget>,
+    // the first placements are split across this many threads, 0 and 1 search on
+    // the calling thread
+    pub threads: usize,
+    pub weights: Weights,
+    // collect `SearchStats`, off by default as timing every expansion slows the search down
+    pub stats: bool,
+}
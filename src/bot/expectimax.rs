@@ -0,0 +1,40 @@
+use crate::bot::{generate_states, heuristic, SearchConfig};
+use crate::{game, pieces};
+
+use std::sync::Arc;
+
+// value of a piece with nowhere to spawn, worse than any placement
+const BLOCKED_OUT: i64 = -1_000_000_000;
+
+// the evaluation one piece past the preview: for each piece in
+// `state.possible_next_pieces` the value of its best placement, averaged
+// None when the possible pieces are unknown
+pub fn expected_value(
+    state: &Arc<game::State>,
+    pieces: &pieces::PieceSet,
+    id_manager: &mut game::IdManager,
+    config: &SearchConfig,
+) -> Option<i32> {
+    let mut possible = state.possible_next_pieces.clone();
+
+    if possible.is_empty() {
+        return None;
+    }
+
+    // pieces listed more than once are that much more likely
+    possible.sort_unstable();
+
+    let mut total = 0;
+
+    for group in possible.chunk_by(|a, b| a == b) {
+        let best = generate_states(state, group[0], pieces, id_manager, false)
+            .iter_mut()
+            .map(|child| heuristic::apply(child, id_manager, &config.weights) as i64)
+            .max()
+            .unwrap_or(BLOCKED_OUT);
+
+        total += best * group.len() as i64;
+    }
+
+    Some((total / possible.len() as i64) as i32)
+}
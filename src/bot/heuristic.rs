@@ -1,25 +1,279 @@
 use crate::game;
 
-pub fn apply(state: &mut game::State, id_manager: &mut game::IdManager) -> i32 {
-    let mut score = 0;
-    let mut penalize_top: i32;
+use std::str::FromStr;
 
-    let cleared_rows = game::update(state, id_manager, 0, true) as i32;
+// feature values are weighted as floats, then scaled into an integer priority
+pub const PRIORITY_SCALE: f64 = 1000.0;
 
-    score += cleared_rows ^ (4 * 9000);
+// board features of a state after its placement has been cleared,
+// rows are counted from the top, column heights from the floor
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Features {
+    // sum of column heights
+    pub aggregate_height: f64,
+    // empty cells with a filled cell somewhere above them
+    pub holes: f64,
+    // sum of height differences between neighbouring columns
+    pub bumpiness: f64,
+    // filled/empty changes along each row, walls count as filled
+    pub row_transitions: f64,
+    // filled/empty changes along each column, the floor counts as filled
+    pub column_transitions: f64,
+    // empty cells with filled (or wall) neighbours on both sides,
+    // summed as 1 + 2 + .. + depth for each well
+    pub well_depths: f64,
+    // filled cells above holes, counted once per hole
+    pub covered_cells: f64,
+    // rows cleared by the placement
+    pub cleared_lines: f64,
+    // points scored by the placement divided by the level, see `game::scoring`
+    pub points: f64,
+}
+
+impl Features {
+    pub fn new(field: &game::GameField, cleared_lines: u32, points: u64) -> Self {
+        let width = field.width();
+        let height = field.height();
+        let occupancy = field.occupancy();
+        let full_mask = field.full_mask();
+
+        let mut features = Features {
+            cleared_lines: cleared_lines as f64,
+            points: points as f64,
+            ..Features::default()
+        };
+
+        let mut column_heights = vec![0; width];
+
+        for (col, column_height) in column_heights.iter_mut().enumerate() {
+            let mut filled_above = 0;
+
+            for row in 0..height {
+                if !field.is_empty(row, col) {
+                    if filled_above == 0 {
+                        *column_height = height - row;
+                    }
+                    filled_above += 1;
+                } else if filled_above > 0 {
+                    features.holes += 1.0;
+                    features.covered_cells += filled_above as f64;
+                }
+            }
+        }
+
+        features.aggregate_height = column_heights.iter().sum::<usize>() as f64;
+        features.bumpiness = column_heights
+            .windows(2)
+            .map(|pair| pair[0].abs_diff(pair[1]))
+            .sum::<usize>() as f64;
+
+        // one bit per neighbouring pair of cells, walls included
+        let pairs_mask = (1u64 << (width + 1)) - 1;
+
+        for &mask in occupancy {
+            // walls on both sides, as bits 0 and width + 1
+            let walled = (mask as u64) << 1 | 1 | 1 << (width + 1);
+            features.row_transitions += ((walled ^ (walled >> 1)) & pairs_mask).count_ones() as f64;
+        }
+
+        for pair in occupancy.windows(2) {
+            features.column_transitions += (pair[0] ^ pair[1]).count_ones() as f64;
+        }
+        if let Some(&bottom) = occupancy.last() {
+            features.column_transitions += (!bottom & full_mask).count_ones() as f64;
+        }
+
+        for col in 0..width {
+            let mut depth = 0;
+
+            for row in 0..height {
+                let left_filled = col == 0 || !field.is_empty(row, col - 1);
+                let right_filled = col + 1 == width || !field.is_empty(row, col + 1);
+
+                if field.is_empty(row, col) && left_filled && right_filled {
+                    depth += 1;
+                    features.well_depths += depth as f64;
+                } else {
+                    depth = 0;
+                }
+            }
+        }
+
+        features
+    }
+
+    // in declaration order, see `FEATURE_NAMES`
+    pub fn to_array(self) -> [f64; FEATURE_COUNT] {
+        [
+            self.aggregate_height,
+            self.holes,
+            self.bumpiness,
+            self.row_transitions,
+            self.column_transitions,
+            self.well_depths,
+            self.covered_cells,
+            self.cleared_lines,
+            self.points,
+        ]
+    }
+
+    pub fn from_array(values: [f64; FEATURE_COUNT]) -> Self {
+        Features {
+            aggregate_height: values[0],
+            holes: values[1],
+            bumpiness: values[2],
+            row_transitions: values[3],
+            column_transitions: values[4],
+            well_depths: values[5],
+            covered_cells: values[6],
+            cleared_lines: values[7],
+            points: values[8],
+        }
+    }
+}
+
+pub const FEATURE_COUNT: usize = 9;
+
+// in declaration order, as used in weights files
+const FEATURE_NAMES: [&str; FEATURE_COUNT] = [
+    "aggregate_height",
+    "holes",
+    "bumpiness",
+    "row_transitions",
+    "column_transitions",
+    "well_depths",
+    "covered_cells",
+    "cleared_lines",
+    "points",
+];
+
+// one weight per feature, in the field of the feature it weighs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weights(Features);
+
+impl std::ops::Deref for Weights {
+    type Target = Features;
+
+    fn deref(&self) -> &Features {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for Weights {
+    fn deref_mut(&mut self) -> &mut Features {
+        &mut self.0
+    }
+}
+
+// hand-picked on the default 5x15 pentomino board
+impl Default for Weights {
+    fn default() -> Self {
+        Weights(Features {
+            aggregate_height: -0.5,
+            holes: -4.0,
+            bumpiness: -0.2,
+            row_transitions: -0.3,
+            column_transitions: -1.0,
+            well_depths: -0.3,
+            covered_cells: -0.5,
+            cleared_lines: 1.0,
+            points: 0.001,
+        })
+    }
+}
+
+impl Weights {
+    pub fn evaluate(&self, features: &Features) -> f64 {
+        self.to_array()
+            .iter()
+            .zip(features.to_array())
+            .map(|(weight, value)| weight * value)
+            .sum()
+    }
+
+    // each feature's share of `evaluate()`, by feature name, in declaration order
+    pub fn contributions(&self, features: &Features) -> [(&'static str, f64); FEATURE_COUNT] {
+        let weights = self.to_array();
+        let values = features.to_array();
+
+        std::array::from_fn(|i| (FEATURE_NAMES[i], weights[i] * values[i]))
+    }
+
+    // in declaration order, for the tuner
+    pub fn from_array(values: [f64; FEATURE_COUNT]) -> Self {
+        Weights(Features::from_array(values))
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("could not read weights file '{}': {}", path, err))?;
+
+        text.parse().map_err(|err| format!("{}: {}", path, err))
+    }
+}
+
+// `<feature> = <weight>` lines, blank lines and lines starting with '#' are ignored
+// features left out keep their default weight
+impl FromStr for Weights {
+    type Err = String;
 
-    for row in 0..game::FIELD_HEIGHT {
-        // score bias towards bottom rows
-        penalize_top = (12 * game::FIELD_HEIGHT as i32 / (row as i32 + 1)) << 13;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = Weights::default().to_array();
 
-        for col in 0..game::FIELD_WIDTH {
-            if state.field[row][col] != game::EMPTY {
-                score -= penalize_top;
-            } else {
-                score += penalize_top;
+        for (line_number, line) in s.lines().enumerate().map(|(i, l)| (i + 1, l.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
             }
+
+            let (feature, value) = line
+                .split_once('=')
+                .ok_or(format!("{}: expected <feature> = <weight>", line_number))?;
+
+            let index = FEATURE_NAMES
+                .iter()
+                .position(|&name| name == feature.trim())
+                .ok_or(format!(
+                    "{}: unknown feature '{}'",
+                    line_number,
+                    feature.trim()
+                ))?;
+
+            weights[index] = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("{}: invalid weight '{}'", line_number, value.trim()))?;
         }
+
+        Ok(Weights::from_array(weights))
     }
+}
+
+// same format as parsed by `from_str()`
+impl std::fmt::Display for Weights {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (name, weight) in FEATURE_NAMES.iter().zip(self.to_array()) {
+            writeln!(f, "{} = {}", name, weight)?;
+        }
+
+        Ok(())
+    }
+}
+
+// clears the placement, then scores the resulting state, higher is better
+pub fn apply(state: &mut game::State, id_manager: &mut game::IdManager, weights: &Weights) -> i32 {
+    let features = clear(state, id_manager);
+
+    // saturates instead of overflowing
+    (weights.evaluate(&features) * PRIORITY_SCALE) as i32
+}
+
+// clears the placement, then measures the resulting state
+pub fn clear(state: &mut game::State, id_manager: &mut game::IdManager) -> Features {
+    let level = state.level() as u64;
+    let score_before = state.score;
+
+    let cleared_rows = game::update(state, id_manager);
 
-    score
+    let points = (state.score - score_before) / level;
+    Features::new(&state.field, cleared_rows, points)
 }
@@ -0,0 +1,232 @@
+use crate::bot::config::Meter;
+use crate::bot::{expand, expectimax, Budget, SearchConfig, SearchStats};
+use crate::{game, pieces};
+
+use std::sync::Arc;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// UCT exploration constant, for values normalized to 0..1
+const EXPLORATION: f64 = 1.4;
+// chance of a rollout placing a piece at random instead of at its best heuristic placement
+const RANDOM_ROLLOUT_RATE: f64 = 0.2;
+
+struct Node {
+    state: Arc<game::State>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    // children not in the tree yet, worst first so pop() takes the most promising,
+    // None until the node is first selected
+    unexpanded: Option<Vec<(i32, game::State)>>,
+    // of `state` itself, after clearing its rows
+    heuristic: i32,
+    visits: u32,
+    total_value: f64,
+}
+
+// the lowest and highest rollout values so far, to bring values into 0..1 for UCT
+struct Bounds {
+    min: f64,
+    max: f64,
+}
+
+impl Bounds {
+    fn normalize(&self, value: f64) -> f64 {
+        if self.max > self.min {
+            (value - self.min) / (self.max - self.min)
+        } else {
+            0.5
+        }
+    }
+}
+
+// UCT over the placements of the known pieces, each iteration selects down the tree,
+// adds one child and finishes the lookahead from there with a heuristic-guided rollout
+// the first placement visited most is chosen
+pub fn search(
+    mut first_generation: Vec<(i32, game::State)>,
+    pieces: &pieces::PieceSet,
+    id_manager: &mut game::IdManager,
+    budget: Budget,
+    config: &SearchConfig,
+    stats: &mut SearchStats,
+) -> Option<(i32, Arc<game::State>)> {
+    let initial_state = Arc::clone(first_generation.first()?.1.parent_state.as_ref()?);
+
+    // seeded from the game, so seeded games with an iteration budget replay the same
+    let mut rng = StdRng::seed_from_u64(initial_state.pieces_placed as u64);
+
+    first_generation.sort_by_key(|(heuristic, _)| *heuristic);
+
+    let mut tree = vec![Node {
+        state: initial_state,
+        parent: None,
+        children: Vec::new(),
+        unexpanded: Some(first_generation),
+        heuristic: 0,
+        visits: 0,
+        total_value: 0.0,
+    }];
+
+    let mut bounds = Bounds {
+        min: f64::INFINITY,
+        max: f64::NEG_INFINITY,
+    };
+
+    // the strategy's own budget, and the one every strategy is held to
+    let mut meter = Meter::new(Some(budget));
+    let mut search_meter = Meter::new(config.budget);
+
+    while !meter.is_exhausted() && !search_meter.is_exhausted() {
+        meter.spend();
+        search_meter.spend();
+
+        let index = select(&mut tree, &bounds, pieces, id_manager, config, stats);
+        stats.record_frontier(tree.len());
+
+        let node = &tree[index];
+        let value = match rollout(
+            &node.state,
+            node.heuristic,
+            pieces,
+            id_manager,
+            config,
+            &mut rng,
+            stats,
+        ) {
+            Some(value) => {
+                bounds.min = bounds.min.min(value);
+                bounds.max = bounds.max.max(value);
+                value
+            }
+            // blocked out before the end of the lookahead, as bad as anything seen so far
+            None if bounds.min.is_finite() => bounds.min,
+            None => node.heuristic as f64,
+        };
+
+        let mut current = Some(index);
+        while let Some(index) = current {
+            tree[index].visits += 1;
+            tree[index].total_value += value;
+            current = tree[index].parent;
+        }
+    }
+
+    let mean_value = |node: &Node| node.total_value / node.visits as f64;
+
+    let best_child = tree[0]
+        .children
+        .iter()
+        .map(|&index| &tree[index])
+        .max_by(|a, b| {
+            a.visits
+                .cmp(&b.visits)
+                .then(mean_value(a).total_cmp(&mean_value(b)))
+        })?;
+
+    Some((mean_value(best_child) as i32, Arc::clone(&best_child.state)))
+}
+
+// walks down by UCT until a node with unexpanded children, then adds one of them
+// stops early at a node with no remaining pieces or no placements
+fn select(
+    tree: &mut Vec<Node>,
+    bounds: &Bounds,
+    pieces: &pieces::PieceSet,
+    id_manager: &mut game::IdManager,
+    config: &SearchConfig,
+    stats: &mut SearchStats,
+) -> usize {
+    let mut index = 0;
+
+    loop {
+        let node = &tree[index];
+
+        if node.state.remaining_pieces.is_empty() {
+            return index;
+        }
+
+        if node.unexpanded.is_none() {
+            let mut children = expand(&node.state, pieces, id_manager, false, config, stats);
+            children.sort_by_key(|(heuristic, _)| *heuristic);
+            tree[index].unexpanded = Some(children);
+        }
+
+        if let Some((heuristic, child)) = tree[index].unexpanded.as_mut().and_then(Vec::pop) {
+            let child_index = tree.len();
+
+            tree.push(Node {
+                state: Arc::new(child),
+                parent: Some(index),
+                children: Vec::new(),
+                unexpanded: None,
+                heuristic,
+                visits: 0,
+                total_value: 0.0,
+            });
+            tree[index].children.push(child_index);
+
+            return child_index;
+        }
+
+        let node = &tree[index];
+        let log_visits = (node.visits as f64).ln();
+
+        let uct = |child: &Node| {
+            bounds.normalize(child.total_value / child.visits as f64)
+                + EXPLORATION * (log_visits / child.visits as f64).sqrt()
+        };
+
+        index = match node
+            .children
+            .iter()
+            .copied()
+            .max_by(|&a, &b| uct(&tree[a]).total_cmp(&uct(&tree[b])))
+        {
+            Some(child_index) => child_index,
+            // blocked out
+            None => return index,
+        };
+    }
+}
+
+// places the rest of the remaining pieces, mostly at their best heuristic placement,
+// returns the value of the final state, None if it gets blocked out first
+fn rollout(
+    state: &Arc<game::State>,
+    heuristic: i32,
+    pieces: &pieces::PieceSet,
+    id_manager: &mut game::IdManager,
+    config: &SearchConfig,
+    rng: &mut StdRng,
+    stats: &mut SearchStats,
+) -> Option<f64> {
+    let mut current = Arc::clone(state);
+    let mut current_heuristic = heuristic;
+
+    while !current.remaining_pieces.is_empty() {
+        let mut children = expand(&current, pieces, id_manager, false, config, stats);
+
+        if children.is_empty() {
+            return None;
+        }
+
+        let pick = if rng.gen_bool(RANDOM_ROLLOUT_RATE) {
+            rng.gen_range(0..children.len())
+        } else {
+            (0..children.len()).max_by_key(|&i| children[i].0)?
+        };
+
+        let (child_heuristic, child) = children.swap_remove(pick);
+        current = Arc::new(child);
+        current_heuristic = child_heuristic;
+    }
+
+    let value = match config.expectimax {
+        true => expectimax::expected_value(&current, pieces, id_manager, config),
+        false => None,
+    };
+
+    Some(value.unwrap_or(current_heuristic) as f64)
+}
@@ -1,194 +1,442 @@
-use crate::{game, pentominoes};
-use std::{collections::HashSet, rc::Rc};
+use crate::{game, pieces};
+use std::{cmp::Reverse, sync::Arc, time::Instant};
 
 use priority_queue::PriorityQueue;
 
+mod adversary;
+mod beam;
+mod config;
+mod expectimax;
 mod heuristic;
+mod mcts;
+mod parallel;
+mod ranking;
+mod stats;
+mod transposition;
 
+pub use adversary::Adversary;
+use config::Meter;
+pub use config::{Budget, SearchConfig, Strategy};
+pub use heuristic::Weights;
+use ranking::rank_placements;
+pub use ranking::RankedPlacement;
+pub use stats::SearchStats;
+use transposition::TranspositionTable;
+
+// where to place the first of `initial_state.remaining_pieces`, chosen by looking
+// ahead at all of them, along with what it took to get there
 pub fn search(
     initial_state: game::State,
-    permutations: &pentominoes::Permutations,
+    pieces: &pieces::PieceSet,
     id_manager: &mut game::IdManager,
-    lookahead_size: &u8,
-) -> Option<game::State> {
-    let mut queue = PriorityQueue::new();
-    let mut visited = HashSet::new();
+    config: &SearchConfig,
+) -> (Option<game::Placement>, SearchStats) {
+    let (leaf, stats) = search_leaf(initial_state, pieces, id_manager, config, |_| {});
 
-    let rc_initial_state = Rc::new(initial_state);
+    (leaf.map(|leaf| first_placement(&leaf)), stats)
+}
 
-    visited.insert(Rc::clone(&rc_initial_state));
-    queue.push(rc_initial_state, 0);
+// the state `search()` takes its answer from, rows cleared, only its first placement is
+// meant to be played: it has not placed all of `initial_state.remaining_pieces` when
+// the budget runs out first, and never does with `Strategy::Mcts`, which answers with
+// a first placement
+// `inspect` is handed the first placements and their values before the strategy runs
+fn search_leaf(
+    initial_state: game::State,
+    pieces: &pieces::PieceSet,
+    id_manager: &mut game::IdManager,
+    config: &SearchConfig,
+    inspect: impl FnOnce(&[(i32, game::State)]),
+) -> (Option<Arc<game::State>>, SearchStats) {
+    let start = Instant::now();
+    let mut stats = SearchStats::new(&initial_state, config.stats);
 
-    loop {
-        let (current_state, _) = queue.pop()?;
+    let arc_initial_state = Arc::new(initial_state);
 
-        if current_state.remaining_pieces.is_empty() {
-            let mut rc_current_state = Rc::clone(&current_state);
-            // return N-1 parent states, where N is App::lookahead_size
-            for _ in 1..*lookahead_size {
-                rc_current_state = match &rc_current_state.parent_state {
-                    Some(parent) => Rc::clone(parent),
-                    // only initial_state has no parent_state
-                    None => break,
-                };
-            }
+    // placed here, so the strategies only ever search below the first placements
+    // and can be split across threads by them
+    let first_generation = expand(
+        &arc_initial_state,
+        pieces,
+        id_manager,
+        true,
+        config,
+        &mut stats,
+    );
+    inspect(&first_generation);
 
-            // access and clone() `uncleared_state` field
-            // then unwrap(), then dereference `Box` containing `State`
-            return Some(*rc_current_state.uncleared_state.clone().unwrap());
+    let answer = match config.threads {
+        0 | 1 => search_from(first_generation, pieces, id_manager, config, &mut stats),
+        threads => parallel::search(
+            first_generation,
+            pieces,
+            id_manager,
+            threads,
+            config,
+            &mut stats,
+        ),
+    };
+
+    stats.total_time = start.elapsed();
+
+    (answer.map(|(_, leaf)| leaf), stats)
+}
+
+// the most promising state the strategy finds below `first_generation`, and its value
+fn search_from(
+    first_generation: Vec<(i32, game::State)>,
+    pieces: &pieces::PieceSet,
+    id_manager: &mut game::IdManager,
+    config: &SearchConfig,
+    stats: &mut SearchStats,
+) -> Option<(i32, Arc<game::State>)> {
+    match config.strategy {
+        Strategy::BestFirst => {
+            best_first_search(first_generation, pieces, id_manager, config, stats)
+        }
+        Strategy::Beam { width } => {
+            beam::search(first_generation, pieces, id_manager, width, config, stats)
+        }
+        Strategy::Mcts { budget } => {
+            mcts::search(first_generation, pieces, id_manager, budget, config, stats)
         }
+    }
+}
 
-        let piece_to_place = current_state.remaining_pieces[0];
-        // generate_states() will only clone() into uncleared_state if is_first_generation
-        let is_first_generation = current_state.remaining_pieces.len() == *lookahead_size as usize;
+// anything that can decide where the next piece goes, so the app and the perf
+// harness can be driven by any strategy, a script or a human alike
+pub trait Bot {
+    fn name(&self) -> String;
 
-        let child_states = generate_states(
-            &current_state,
-            piece_to_place,
-            permutations,
-            id_manager,
-            is_first_generation,
-        );
+    // where to place the first of `state.remaining_pieces`, the rest of them is the
+    // preview, see `game::apply_placement()`
+    // None if no placement is good enough, e.g. every line of the preview tops out
+    fn choose(
+        &mut self,
+        state: &game::State,
+        id_manager: &mut game::IdManager,
+    ) -> Option<game::Placement>;
 
-        for mut child in child_states {
-            let heuristic = heuristic::apply(&mut child, id_manager);
-            let rc_child = Rc::new(child);
+    // of the last `choose()`, for bots that search
+    fn stats(&self) -> Option<&SearchStats> {
+        None
+    }
 
-            if visited.insert(Rc::clone(&rc_child)) {
-                queue.push(rc_child, heuristic);
-            }
+    // the placement played by the last `choose()`, then the best of the others,
+    // for bots that can explain their choice
+    fn ranking(&self) -> &[RankedPlacement] {
+        &[]
+    }
+}
+
+// `search()` with a fixed config, looking ahead at the whole preview
+pub struct SearchBot {
+    pieces: pieces::PieceSet,
+    config: SearchConfig,
+    last_stats: Option<SearchStats>,
+    // placements kept in `last_ranking`, 0 skips ranking
+    ranking_size: usize,
+    last_ranking: Vec<RankedPlacement>,
+}
+
+impl SearchBot {
+    pub fn new(pieces: pieces::PieceSet, config: SearchConfig) -> Self {
+        SearchBot {
+            pieces,
+            config,
+            last_stats: None,
+            ranking_size: 0,
+            last_ranking: Vec::new(),
+        }
+    }
+
+    // ranks up to `k` placements on every `choose()`, see `Bot::ranking()`
+    pub fn with_ranking(self, k: usize) -> Self {
+        SearchBot {
+            ranking_size: k,
+            ..self
         }
     }
 }
 
-fn generate_states(
-    rc_parent_state: &Rc<game::State>,
-    piece: char,
-    permutations: &pentominoes::Permutations,
-    id_manager: &mut game::IdManager,
-    is_first_generation: bool,
-) -> Vec<game::State> {
-    let mut states = Vec::new();
+impl Bot for SearchBot {
+    fn name(&self) -> String {
+        let mut name = self.config.strategy.to_string();
 
-    let pent_id = pentominoes::char_to_id(piece);
-    let composite_id = game::create_composite_id(pent_id, id_manager.next_unique_id(pent_id));
-
-    for mutation in &permutations[pent_id as usize] {
-        for row in 0..=(game::FIELD_HEIGHT - mutation.len()) {
-            for col in 0..=(game::FIELD_WIDTH - mutation[0].len()) {
-                // [row][col] is top-left of 2d vec 'mutation'
-                if !can_place(rc_parent_state.field.as_ref(), mutation, row, col) {
-                    continue;
-                }
-
-                let mut child_state = game::State {
-                    // https://rust-lang.github.io/rust-clippy/master/index.html#needless_borrow
-                    // before: parent_state: Some(&rc_parent_state)
-                    // after:
-                    parent_state: Some(Rc::clone(rc_parent_state)),
-                    uncleared_state: None,
-                    field: place_piece(
-                        rc_parent_state.field.clone(),
-                        mutation,
-                        composite_id,
-                        row,
-                        col,
-                    ),
-                    cleared_rows: rc_parent_state.cleared_rows,
-                    remaining_pieces: rc_parent_state.remaining_pieces.clone(),
-                };
+        if self.config.expectimax {
+            name += ", expectimax";
+        }
+        if let Some(budget) = self.config.budget {
+            name += &format!(", budget {}", budget);
+        }
+        if self.config.threads > 1 {
+            name += &format!(", {} threads", self.config.threads);
+        }
+
+        name
+    }
 
-                if is_first_generation {
-                    child_state.uncleared_state = Some(Box::new(child_state.clone()));
-                }
+    fn choose(
+        &mut self,
+        state: &game::State,
+        id_manager: &mut game::IdManager,
+    ) -> Option<game::Placement> {
+        if self.ranking_size == 0 {
+            let (solution, stats) = search(state.clone(), &self.pieces, id_manager, &self.config);
 
-                child_state.remaining_pieces.remove(0);
-                states.push(child_state);
-            }
+            self.last_stats = self.config.stats.then_some(stats);
+            return solution;
         }
+
+        // ranked from the same search, so the placement played is among them
+        let mut first_generation = Vec::new();
+
+        let (leaf, stats) = search_leaf(
+            state.clone(),
+            &self.pieces,
+            id_manager,
+            &self.config,
+            |children| first_generation = children.to_vec(),
+        );
+        let solution = leaf.map(|leaf| first_placement(&leaf));
+
+        self.last_stats = self.config.stats.then_some(stats);
+        self.last_ranking = rank_placements(
+            state,
+            &first_generation,
+            solution.as_ref(),
+            &self.pieces,
+            &self.config.weights,
+            self.ranking_size,
+        );
+        solution
     }
 
-    states
+    fn stats(&self) -> Option<&SearchStats> {
+        self.last_stats.as_ref()
+    }
+
+    fn ranking(&self) -> &[RankedPlacement] {
+        &self.last_ranking
+    }
 }
 
-// https://rust-lang.github.io/rust-clippy/master/index.html#ptr_arg
-// before: mutation: &Vec<Vec<u8>>
-// after: mutation: &[Vec<u8>]
-fn can_place(field: &game::GameField, mutation: &[Vec<u8>], row: usize, col: usize) -> bool {
-    let mut floating_tiles = 0;
+fn best_first_search(
+    first_generation: Vec<(i32, game::State)>,
+    pieces: &pieces::PieceSet,
+    id_manager: &mut game::IdManager,
+    config: &SearchConfig,
+    stats: &mut SearchStats,
+) -> Option<(i32, Arc<game::State>)> {
+    let mut queue = PriorityQueue::new();
+    let mut transpositions = TranspositionTable::new();
+
+    let mut meter = Meter::new(config.budget);
+    // the deepest state generated, the most promising among those, answered once
+    // the budget runs out
+    let mut best_so_far: Option<(i32, Arc<game::State>)> = None;
+
+    let mut child_states = first_generation;
 
-    for delta_row in 0..mutation.len() {
-        for delta_col in 0..mutation[0].len() {
-            // mutation is a 2d vec of 0s and 1s
-            if mutation[delta_row][delta_col] == 0 {
+    loop {
+        for (mut heuristic, child) in child_states {
+            // the same position reached before at least as well, e.g. by placing the same
+            // pieces in another order, see `State::zobrist()`
+            let depth = child.remaining_pieces.len() as u8;
+            if !transpositions.improve(child.zobrist(), heuristic, depth) {
+                stats.record_transposition_hits(1);
                 continue;
             }
 
-            // start from top-left of mutation
-            let tile_row = row + delta_row;
-            let tile_col = col + delta_col;
+            let arc_child = Arc::new(child);
 
-            // if tile is out of bounds
-            if tile_col >= field[0].len() || tile_row >= field.len() {
-                return false;
+            if config.expectimax && arc_child.remaining_pieces.is_empty() {
+                heuristic = expectimax::expected_value(&arc_child, pieces, id_manager, config)
+                    .unwrap_or(heuristic);
             }
 
-            // if overlapping with other tiles
-            if field[tile_row][tile_col] != game::EMPTY {
-                return false;
+            let is_better = best_so_far.as_ref().is_none_or(|(best, best_state)| {
+                answer_key(heuristic, &arc_child) > answer_key(*best, best_state)
+            });
+            if is_better {
+                best_so_far = Some((heuristic, Arc::clone(&arc_child)));
             }
 
-            // if not at the bottom of the field
-            if tile_row < field.len() - 1 {
-                // if bottom-most mutation tile
-                if delta_row == mutation.len() - 1 {
-                    if field[tile_row + 1][tile_col] == game::EMPTY {
-                        // tile is floating
-                        floating_tiles += 1;
-                    }
-                } else if mutation[delta_row + 1][delta_col] == 0
-                    && field[tile_row + 1][tile_col] == game::EMPTY
-                {
-                    floating_tiles += 1;
-                }
-            }
+            queue.push(arc_child, heuristic);
+        }
 
-            // piece is floating
-            if floating_tiles >= mutation[0].len() {
-                return false;
-            }
+        stats.record_frontier(queue.len());
+
+        let (current_state, heuristic) = queue.pop()?;
 
-            // actually place the tile
-            // field[tile_row][tile_col] = composite_id;
+        if current_state.remaining_pieces.is_empty() {
+            return Some((heuristic, current_state));
         }
+
+        if meter.is_exhausted() {
+            return best_so_far;
+        }
+        meter.spend();
+
+        child_states = expand(&current_state, pieces, id_manager, false, config, stats);
     }
+}
 
-    true
+// how answers cut short by a budget compare: the deepest first, then the most promising
+fn answer_key(heuristic: i32, state: &game::State) -> (Reverse<usize>, i32) {
+    (Reverse(state.remaining_pieces.len()), heuristic)
 }
 
-fn place_piece(
-    mut field: game::GameField,
-    // https://rust-lang.github.io/rust-clippy/master/index.html#ptr_arg
-    // before: mutation: &Vec<Vec<u8>>,
-    // after:
-    mutation: &[Vec<u8>],
-    composite_id: u16,
-    row: usize,
-    col: usize,
-) -> game::GameField {
-    for delta_row in 0..mutation.len() {
-        for delta_col in 0..mutation[0].len() {
-            if mutation[delta_row][delta_col] == 0 {
-                continue;
-            }
+// the placement of the first generation ancestor of `state`
+fn first_placement(state: &Arc<game::State>) -> game::Placement {
+    let mut current_state = state;
+
+    // only first generation states keep their `placement`
+    loop {
+        if let Some(placement) = &current_state.placement {
+            return placement.clone();
+        }
+
+        current_state = match &current_state.parent_state {
+            Some(parent) => parent,
+            None => panic!("only descendants of the initial state have a first placement"),
+        };
+    }
+}
+
+// every placement of the next piece, cleared and scored by the heuristic
+fn expand(
+    arc_state: &Arc<game::State>,
+    pieces: &pieces::PieceSet,
+    id_manager: &mut game::IdManager,
+    is_first_generation: bool,
+    config: &SearchConfig,
+    stats: &mut SearchStats,
+) -> Vec<(i32, game::State)> {
+    // reading the clock is what collecting stats costs the most
+    let start = stats.is_recording().then(Instant::now);
+
+    // generate_states() only records the placement if is_first_generation
+    let children: Vec<(i32, game::State)> = generate_states(
+        arc_state,
+        arc_state.remaining_pieces[0],
+        pieces,
+        id_manager,
+        is_first_generation,
+    )
+    .into_iter()
+    .map(|mut child| {
+        (
+            heuristic::apply(&mut child, id_manager, &config.weights),
+            child,
+        )
+    })
+    .collect();
+
+    if let Some(start) = start {
+        stats.record_expansion(arc_state, children.len(), start);
+    }
+    children
+}
+
+// every placement of `piece_id`, and if the rules allow holding, every placement of
+// the piece holding it swaps in
+fn generate_states(
+    arc_parent_state: &Arc<game::State>,
+    piece_id: u8,
+    pieces: &pieces::PieceSet,
+    id_manager: &mut game::IdManager,
+    is_first_generation: bool,
+) -> Vec<game::State> {
+    // the pieces that can be placed, and whether they need a hold
+    let mut options = vec![(piece_id, false)];
+
+    if arc_parent_state.config.hold {
+        // with nothing held, holding brings in the piece after `piece_id`
+        let swapped_piece = match arc_parent_state.held_piece {
+            Some(held_piece) if held_piece != piece_id => Some(held_piece),
+            // the same piece, nothing new to place
+            Some(_) => None,
+            None => arc_parent_state.remaining_pieces.get(1).copied(),
+        };
+
+        options.extend(swapped_piece.map(|swapped_piece| (swapped_piece, true)));
+    }
+
+    let mut states = Vec::new();
+
+    for (placed_piece, hold) in options {
+        let composite_id = game::create_composite_id(placed_piece, id_manager.next_unique_id());
 
-            let tile_row = row + delta_row;
-            let tile_col = col + delta_col;
+        let piece_moves = pieces.moves(placed_piece);
 
-            field[tile_row][tile_col] = composite_id;
+        // also used to look one piece past the preview, see `expectimax`
+        let consumed = match hold && arc_parent_state.held_piece.is_none() {
+            true => 2,
+            false => 1,
         }
+        .min(arc_parent_state.remaining_pieces.len());
+
+        // only placements a falling piece can actually reach from spawn
+        states.extend(
+            piece_moves
+                .reachable_landings(&arc_parent_state.field)
+                .into_iter()
+                .map(|landing| {
+                    let mut child_state = game::State {
+                        // https://rust-lang.github.io/rust-clippy/master/index.html#needless_borrow
+                        // before: parent_state: Some(&arc_parent_state)
+                        // after:
+                        parent_state: Some(Arc::clone(arc_parent_state)),
+                        placement: None,
+                        field: place_piece(
+                            arc_parent_state.field.clone(),
+                            &piece_moves.shapes[landing.permutation],
+                            composite_id,
+                            landing.row,
+                            landing.col,
+                        ),
+                        cleared_rows: arc_parent_state.cleared_rows,
+                        score: arc_parent_state.score,
+                        combo: arc_parent_state.combo,
+                        pieces_placed: arc_parent_state.pieces_placed + 1,
+                        status: arc_parent_state.status,
+                        remaining_pieces: arc_parent_state.remaining_pieces[consumed..].to_vec(),
+                        possible_next_pieces: arc_parent_state.possible_next_pieces.clone(),
+                        held_piece: match hold {
+                            true => Some(piece_id),
+                            false => arc_parent_state.held_piece,
+                        },
+                        config: arc_parent_state.config,
+                    };
+
+                    if is_first_generation {
+                        child_state.placement = Some(game::Placement {
+                            piece_id: placed_piece,
+                            permutation: landing.permutation,
+                            row: landing.row,
+                            col: landing.col,
+                            path: landing.path,
+                            hold,
+                        });
+                    }
+
+                    child_state
+                }),
+        );
     }
 
+    states
+}
+
+fn place_piece(
+    mut field: game::GameField,
+    shape: &game::ShapeMask,
+    composite_id: u32,
+    row: usize,
+    col: usize,
+) -> game::GameField {
+    field.place(shape, composite_id, row, col);
     field
 }
 
@@ -198,11 +446,26 @@ mod tests {
     use super::*;
     use game::EMPTY;
 
+    // a default board with a well only the I piece fills completely
+    fn i_well_state(lookahead_size: u8) -> game::State {
+        let filler = game::create_composite_id(9, 0);
+
+        let mut state = game::State::new(lookahead_size, game::GameConfig::default());
+        let mut field = vec![vec![EMPTY; 5]; 15];
+        for row in field.iter_mut().skip(10) {
+            *row = vec![filler, filler, filler, filler, EMPTY];
+        }
+        state.field = field.into();
+
+        state
+    }
+
     #[test]
     fn test_try_place() {
-        let mut state = game::State::new(crate::DEFAULT_LOOKAHEAD_SIZE);
+        let mut state =
+            game::State::new(crate::DEFAULT_LOOKAHEAD_SIZE, game::GameConfig::default());
 
-        state.remaining_pieces = vec!['P', 'N', 'F'];
+        state.remaining_pieces = vec![9, 10, 11];
 
         let p_composite_id = game::create_composite_id(9, 0);
 
@@ -222,24 +485,82 @@ mod tests {
             vec![EMPTY, EMPTY, p_composite_id, EMPTY, EMPTY],
             vec![EMPTY, EMPTY, EMPTY, EMPTY, EMPTY],
             vec![EMPTY, EMPTY, EMPTY, EMPTY, EMPTY],
-        ];
+        ]
+        .into();
 
-        let x_composite_id = game::create_composite_id(0, 0);
-        let x_piece = vec![vec![0, 1, 0], vec![1, 1, 1], vec![0, 1, 0]];
+        let pieces = pieces::PieceSet::pentominoes();
+
+        let x_moves = pieces.moves(0);
+        let x_landings = x_moves.reachable_landings(&state.field);
+
+        let l_moves = pieces.moves(8);
+        let l_landings = l_moves.reachable_landings(&state.field);
 
-        let l_composite_id = game::create_composite_id(8, 0);
         let l_piece = vec![vec![1, 0], vec![1, 0], vec![1, 0], vec![1, 1]];
+        let l_permutation = l_moves
+            .shapes
+            .iter()
+            .position(|shape| *shape == game::ShapeMask::new(&l_piece))
+            .unwrap();
+
+        // resting on the P tile
+        assert!(l_landings
+            .iter()
+            .any(|l| (l.permutation, l.row, l.col) == (l_permutation, 8, 1)));
+        // overlapping the P tile
+        assert!(!x_landings.iter().any(|l| (l.row, l.col) == (10, 1)));
+
+        // every path replays to the landing it was generated for
+        for landing in l_landings.iter().chain(&x_landings) {
+            let moves = if x_landings.contains(landing) {
+                x_moves
+            } else {
+                l_moves
+            };
+
+            assert_eq!(
+                moves.follow_path(&state.field, &landing.path),
+                Some((landing.permutation, landing.row, landing.col))
+            );
+        }
+
+        println!("{}", state.display(&pieces));
+    }
+
+    #[test]
+    fn test_unreachable_cavity() {
+        let mut state =
+            game::State::new(crate::DEFAULT_LOOKAHEAD_SIZE, game::GameConfig::default());
+
+        let roof = game::create_composite_id(9, 0);
+
+        let mut field = vec![vec![EMPTY; 5]; 15];
+        // roof over the two bottom rows, only open in the rightmost column
+        field[12] = vec![roof, roof, roof, roof, EMPTY];
+        state.field = field.into();
 
-        println!("result: {}", can_place(&state.field, &l_piece, 8, 1));
+        let pieces = pieces::PieceSet::pentominoes();
+        let i_moves = pieces.moves(1);
 
-        println!("{}", state);
+        let landings = i_moves.reachable_landings(&state.field);
+
+        assert!(!landings.is_empty());
+
+        // the vertical I can drop through the gap, the horizontal I cannot get under the roof
+        for landing in &landings {
+            let shape = &i_moves.shapes[landing.permutation];
+            assert!(landing.row + shape.height <= 12 || landing.col == 4);
+        }
+
+        assert!(landings.iter().any(|l| (l.row, l.col) == (10, 4)));
     }
 
     #[test]
     fn test_heuristic() {
-        let mut state_a = game::State::new(crate::DEFAULT_LOOKAHEAD_SIZE);
+        let mut state_a =
+            game::State::new(crate::DEFAULT_LOOKAHEAD_SIZE, game::GameConfig::default());
 
-        state_a.remaining_pieces = vec!['X', 'I', 'Z', 'T', 'U'];
+        state_a.remaining_pieces = vec![0, 1, 2, 3, 4];
 
         state_a.field = vec![
             vec![1, 2, 3, 4, EMPTY],
@@ -257,11 +578,13 @@ mod tests {
             vec![EMPTY, EMPTY, EMPTY, EMPTY, EMPTY],
             vec![EMPTY, EMPTY, EMPTY, EMPTY, EMPTY],
             vec![EMPTY, EMPTY, EMPTY, EMPTY, EMPTY],
-        ];
+        ]
+        .into();
 
-        let mut state_b = game::State::new(crate::DEFAULT_LOOKAHEAD_SIZE);
+        let mut state_b =
+            game::State::new(crate::DEFAULT_LOOKAHEAD_SIZE, game::GameConfig::default());
 
-        state_b.remaining_pieces = vec!['X', 'I', 'Z', 'T', 'U'];
+        state_b.remaining_pieces = vec![0, 1, 2, 3, 4];
 
         state_b.field = vec![
             vec![EMPTY, EMPTY, EMPTY, EMPTY, EMPTY],
@@ -279,20 +602,537 @@ mod tests {
             vec![1, 2, 3, 4, EMPTY],
             vec![1, 2, 3, 4, EMPTY],
             vec![1, 2, 3, 4, EMPTY],
-        ];
+        ]
+        .into();
 
         let mut id_manager = game::IdManager::default();
 
-        let heuristic_a = heuristic::apply(&mut state_a, &mut id_manager);
-        let heuristic_b = heuristic::apply(&mut state_b, &mut id_manager);
+        let weights = Weights::default();
+
+        let heuristic_a = heuristic::apply(&mut state_a, &mut id_manager, &weights);
+        let heuristic_b = heuristic::apply(&mut state_b, &mut id_manager, &weights);
 
         println!("HEURISTIC A: {}", heuristic_a);
 
         println!("HEURISTIC B: {}", heuristic_b);
 
         // heuristic_a should be greater than heuristic_b
-        assert!(state_a.field.len() == 15);
-        assert!(state_b.field.len() == 15);
+        assert!(state_a.field.height() == 15);
+        assert!(state_b.field.height() == 15);
         assert!(heuristic_a < heuristic_b);
     }
+
+    #[test]
+    fn test_adversary() {
+        use game::Randomizer;
+        use rand::SeedableRng;
+
+        let state = i_well_state(crate::DEFAULT_LOOKAHEAD_SIZE);
+
+        let pieces = pieces::PieceSet::pentominoes();
+        let mut adversary = Adversary::new(pieces.clone(), SearchConfig::default());
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+
+        for _ in 0..5 {
+            let shape = adversary.next_shape(&mut rng, &state);
+            assert_ne!(pieces.symbol(shape), 'I');
+        }
+
+        // with a U drawn before it, the draw is judged on the board the U is left on
+        let mut preview = state.clone();
+        preview.remaining_pieces = vec![pieces.id_of('U').unwrap()];
+
+        let projected = adversary.project(&preview).unwrap();
+        assert!(projected.remaining_pieces.is_empty());
+        assert_eq!(projected.pieces_placed, 1);
+
+        // the whole preview is placed whatever the bot's strategy and budget
+        let config = SearchConfig {
+            strategy: Strategy::Mcts {
+                budget: Budget::Iterations(10),
+            },
+            budget: Some(Budget::Iterations(2)),
+            ..SearchConfig::default()
+        };
+        let mut budgeted = Adversary::new(pieces.clone(), config);
+
+        let mut long_preview = state.clone();
+        long_preview.remaining_pieces = "UIX".chars().map(|c| pieces.id_of(c).unwrap()).collect();
+
+        let long_projected = budgeted.project(&long_preview).unwrap();
+        assert!(long_projected.remaining_pieces.is_empty());
+        assert_eq!(long_projected.pieces_placed, 3);
+
+        let mut draw = |state: &game::State| adversary.next_shape(&mut rng, state);
+        let stale = draw(&state);
+        let shape = draw(&preview);
+
+        assert_eq!(shape, draw(&projected));
+        assert_ne!(shape, stale);
+    }
+
+    #[test]
+    fn test_features() {
+        let x = game::create_composite_id(0, 0);
+
+        let mut field = vec![vec![EMPTY; 5]; 4];
+        field[1] = vec![EMPTY, x, EMPTY, EMPTY, EMPTY];
+        field[2] = vec![x, EMPTY, EMPTY, EMPTY, x];
+        field[3] = vec![x, x, EMPTY, x, x];
+        let field: game::GameField = field.into();
+
+        let features = heuristic::Features::new(&field, 2, 300);
+
+        // column heights 2, 3, 0, 1, 2
+        assert_eq!(features.aggregate_height, 8.0);
+        assert_eq!(features.holes, 1.0);
+        assert_eq!(features.covered_cells, 1.0);
+        assert_eq!(features.bumpiness, (1 + 3 + 1 + 1) as f64);
+        // 2 per empty row, walls included
+        assert_eq!(features.row_transitions, (2 + 4 + 2 + 2) as f64);
+        // the floor under column 2 included
+        assert_eq!(features.column_transitions, (1 + 3 + 2 + 1) as f64);
+        // column 2 at the bottom, and column 0 against the wall
+        assert_eq!(features.well_depths, (1 + 1) as f64);
+        assert_eq!((features.cleared_lines, features.points), (2.0, 300.0));
+
+        let weights: Weights = "# comment\nholes = -10\n\npoints=0.5".parse().unwrap();
+        assert_eq!(weights.holes, -10.0);
+        assert_eq!(weights.points, 0.5);
+        assert_eq!(weights.bumpiness, Weights::default().bumpiness);
+        assert_eq!(weights.to_string().parse::<Weights>(), Ok(weights));
+
+        assert!("holes".parse::<Weights>().is_err());
+        assert!("height = 1".parse::<Weights>().is_err());
+        assert!("holes = a lot".parse::<Weights>().is_err());
+    }
+
+    #[test]
+    fn test_strategies() {
+        assert_eq!("best-first".parse(), Ok(Strategy::BestFirst));
+        assert_eq!("beam:4".parse(), Ok(Strategy::Beam { width: 4 }));
+        assert_eq!(
+            "beam".parse(),
+            Ok(Strategy::Beam {
+                width: config::DEFAULT_BEAM_WIDTH
+            })
+        );
+        assert!("beam:0".parse::<Strategy>().is_err());
+        assert!("depth-first".parse::<Strategy>().is_err());
+
+        let mcts_time = Strategy::Mcts {
+            budget: Budget::Time(std::time::Duration::from_millis(20)),
+        };
+        assert_eq!("mcts:20ms".parse(), Ok(mcts_time));
+        assert_eq!(mcts_time.to_string().parse(), Ok(mcts_time));
+        assert_eq!(
+            "mcts:300".parse(),
+            Ok(Strategy::Mcts {
+                budget: Budget::Iterations(300)
+            })
+        );
+        assert!("mcts:0".parse::<Strategy>().is_err());
+        assert!("mcts:fast".parse::<Strategy>().is_err());
+
+        let pieces = pieces::PieceSet::pentominoes();
+        let mut id_manager = game::IdManager::default();
+
+        let mut state = game::State::new(3, game::GameConfig::default());
+        state.remaining_pieces = vec![1, 8, 9];
+
+        for strategy in [
+            Strategy::BestFirst,
+            Strategy::Beam { width: 1 },
+            Strategy::Beam { width: 16 },
+            Strategy::Mcts {
+                budget: Budget::Iterations(50),
+            },
+        ] {
+            let config = SearchConfig {
+                strategy,
+                ..SearchConfig::default()
+            };
+
+            let solution = search(state.clone(), &pieces, &mut id_manager, &config)
+                .0
+                .unwrap();
+
+            // the same search, behind the `Bot` trait
+            let mut bot = SearchBot::new(pieces.clone(), config);
+            let chosen = bot.choose(&state, &mut id_manager).unwrap();
+            assert_eq!(chosen, solution);
+
+            // only the first piece is placed
+            let mut placed = state.clone();
+            game::apply_placement(&mut placed, &solution, &pieces, &mut id_manager).unwrap();
+            assert_eq!(placed.pieces_placed, 1);
+            assert_eq!(placed.remaining_pieces, vec![8, 9]);
+            assert_eq!(
+                placed
+                    .field
+                    .occupancy()
+                    .iter()
+                    .map(|mask| mask.count_ones())
+                    .sum::<u32>(),
+                5
+            );
+
+            // the same placement breaks the rules once the piece is gone
+            let mut replayed = placed.clone();
+            assert!(
+                game::apply_placement(&mut replayed, &solution, &pieces, &mut id_manager).is_err()
+            );
+            assert_eq!(replayed.field, placed.field);
+        }
+    }
+
+    #[test]
+    fn test_apply_placement() {
+        let pieces = pieces::PieceSet::pentominoes();
+        let mut id_manager = game::IdManager::default();
+
+        let mut state = game::State::new(1, game::GameConfig::default());
+        state.remaining_pieces = vec![1];
+
+        let landing = pieces.moves(1).reachable_landings(&state.field).remove(0);
+        let placement = game::Placement {
+            piece_id: 1,
+            permutation: landing.permutation,
+            row: landing.row,
+            col: landing.col,
+            path: landing.path,
+            hold: false,
+        };
+
+        // somewhere the path doesn't lead
+        let mut elsewhere = placement.clone();
+        elsewhere.row -= 1;
+        assert!(game::apply_placement(&mut state, &elsewhere, &pieces, &mut id_manager).is_err());
+
+        // not dropped
+        let mut floating = placement.clone();
+        floating.path.pop();
+        assert!(game::apply_placement(&mut state, &floating, &pieces, &mut id_manager).is_err());
+
+        assert_eq!(state.pieces_placed, 0);
+        assert_eq!(state.field, game::GameField::new(5, 15));
+
+        game::apply_placement(&mut state, &placement, &pieces, &mut id_manager).unwrap();
+        assert_eq!(state.pieces_placed, 1);
+        assert!(state.remaining_pieces.is_empty());
+    }
+
+    #[test]
+    fn test_hold() {
+        let pieces = pieces::PieceSet::pentominoes();
+        let mut id_manager = game::IdManager::default();
+
+        let i = pieces.id_of('I').unwrap();
+        let x = pieces.id_of('X').unwrap();
+
+        // the X has to come before the I
+        let mut state = i_well_state(2);
+        state.remaining_pieces = vec![x, i];
+
+        let without_hold =
+            generate_states(&Arc::new(state.clone()), x, &pieces, &mut id_manager, true);
+        assert!(without_hold.iter().all(|child| child.held_piece.is_none()));
+
+        state.config.hold = true;
+
+        let children = generate_states(&Arc::new(state.clone()), x, &pieces, &mut id_manager, true);
+        let held: Vec<&game::State> = children
+            .iter()
+            .filter(|child| child.held_piece == Some(x))
+            .collect();
+
+        assert_eq!(children.len() - held.len(), without_hold.len());
+        // nothing was held, so holding the X brings in the I and uses up the preview
+        assert!(held.iter().all(|child| {
+            child.remaining_pieces.is_empty() && child.placement.as_ref().unwrap().piece_id == i
+        }));
+
+        // the I goes down the well right away instead of after the X
+        let config = SearchConfig::default();
+        let solution = search(state.clone(), &pieces, &mut id_manager, &config)
+            .0
+            .unwrap();
+        assert!(solution.hold);
+        assert_eq!(solution.piece_id, i);
+
+        let mut placed = state.clone();
+        game::apply_placement(&mut placed, &solution, &pieces, &mut id_manager).unwrap();
+        assert_eq!(placed.held_piece, Some(x));
+        assert!(placed.remaining_pieces.is_empty());
+        assert_eq!(game::update(&mut placed, &mut id_manager), 5);
+
+        // the X comes back out of hold, with nothing left to hold instead
+        placed.remaining_pieces = vec![i];
+        let children = generate_states(&Arc::new(placed), i, &pieces, &mut id_manager, false);
+        assert!(children.iter().any(|child| child.held_piece == Some(i)));
+
+        state.config.hold = false;
+        assert!(game::apply_placement(&mut state, &solution, &pieces, &mut id_manager).is_err());
+    }
+
+    #[test]
+    fn test_expectimax() {
+        let pieces = pieces::PieceSet::pentominoes();
+        let mut id_manager = game::IdManager::default();
+        let default_config = SearchConfig::default();
+
+        let state = i_well_state(0);
+
+        let i = pieces.id_of('I').unwrap();
+        let x = pieces.id_of('X').unwrap();
+
+        let mut expected_value = |possible: Vec<u8>| {
+            let mut state = state.clone();
+            state.possible_next_pieces = possible;
+            expectimax::expected_value(&Arc::new(state), &pieces, &mut id_manager, &default_config)
+        };
+
+        assert_eq!(expected_value(vec![]), None);
+
+        let only_i = expected_value(vec![i]).unwrap();
+        let only_x = expected_value(vec![x]).unwrap();
+        let mostly_i = expected_value(vec![i, i, i, x]).unwrap();
+
+        assert!(only_i > mostly_i && mostly_i > only_x);
+        // weighted by how often each piece is listed
+        assert!((mostly_i - (3 * only_i + only_x) / 4).abs() <= 1);
+
+        let mut state = game::State::new(2, game::GameConfig::default());
+        state.remaining_pieces = vec![1, 8];
+        state.possible_next_pieces = (0..pieces.len() as u8).collect();
+
+        for strategy in [Strategy::BestFirst, Strategy::Beam { width: 8 }] {
+            let config = SearchConfig {
+                strategy,
+                expectimax: true,
+                ..SearchConfig::default()
+            };
+
+            let solution = search(state.clone(), &pieces, &mut id_manager, &config)
+                .0
+                .unwrap();
+            assert_eq!(solution.piece_id, 1);
+        }
+    }
+
+    #[test]
+    fn test_budget() {
+        let pieces = pieces::PieceSet::pentominoes();
+        let mut id_manager = game::IdManager::default();
+
+        let mut state = game::State::new(5, game::GameConfig::default());
+        state.remaining_pieces = vec![1, 8, 9, 2, 3];
+
+        for strategy in [
+            Strategy::BestFirst,
+            Strategy::Beam { width: 16 },
+            Strategy::Mcts {
+                budget: Budget::Iterations(500),
+            },
+        ] {
+            for budget in [
+                Budget::Iterations(1),
+                Budget::Iterations(20),
+                Budget::Time(std::time::Duration::ZERO),
+            ] {
+                let config = SearchConfig {
+                    strategy,
+                    budget: Some(budget),
+                    ..SearchConfig::default()
+                };
+
+                // cut short, but still a placement of the first piece
+                let solution = search(state.clone(), &pieces, &mut id_manager, &config)
+                    .0
+                    .unwrap_or_else(|| {
+                        panic!("no answer from {} with budget {}", strategy, budget)
+                    });
+                assert_eq!(solution.piece_id, 1);
+            }
+        }
+
+        let mut meter = Meter::new(Some(Budget::Iterations(2)));
+        assert!(!meter.is_exhausted());
+        meter.spend();
+        assert!(!meter.is_exhausted());
+        meter.spend();
+        assert!(meter.is_exhausted());
+    }
+
+    #[test]
+    fn test_threads() {
+        let pieces = pieces::PieceSet::pentominoes();
+        let mut id_manager = game::IdManager::default();
+
+        let mut state = game::State::new(3, game::GameConfig::default());
+        state.remaining_pieces = vec![1, 8, 9];
+
+        for strategy in [
+            Strategy::BestFirst,
+            Strategy::Beam { width: 8 },
+            Strategy::Mcts {
+                budget: Budget::Iterations(100),
+            },
+        ] {
+            let config = SearchConfig {
+                strategy,
+                threads: 3,
+                stats: true,
+                ..SearchConfig::default()
+            };
+
+            let (solution, stats) = search(state.clone(), &pieces, &mut id_manager, &config);
+            let solution = solution.unwrap();
+            assert_eq!(solution.piece_id, 1);
+
+            // the threads' counters end up in the stats of the one search
+            assert_eq!(stats.searches, 1);
+            assert!(stats.nodes_expanded > 1);
+            assert!(stats.branching_factor() > 1.0);
+            assert!(stats.time_per_depth.len() <= 3);
+
+            // the merge doesn't depend on which thread finishes first
+            let again = search(state.clone(), &pieces, &mut id_manager, &config)
+                .0
+                .unwrap();
+            assert_eq!(again, solution);
+        }
+
+        // a worker that ran out of budget a piece short loses to one that reached the full
+        // depth, however promising its shallower state
+        let x = pieces.id_of('X').unwrap();
+        state.remaining_pieces = vec![x, x, x];
+
+        let config = SearchConfig::default();
+        let mut stats = SearchStats::new(&state, false);
+        let first_generation = expand(
+            &Arc::new(state.clone()),
+            &pieces,
+            &mut id_manager,
+            true,
+            &config,
+            &mut stats,
+        );
+
+        let budgeted = SearchConfig {
+            budget: Some(Budget::Iterations(1)),
+            ..config
+        };
+        let shallow = search_from(
+            first_generation.clone(),
+            &pieces,
+            &mut id_manager,
+            &budgeted,
+            &mut stats,
+        )
+        .unwrap();
+        let deep = search_from(
+            first_generation,
+            &pieces,
+            &mut id_manager,
+            &config,
+            &mut stats,
+        )
+        .unwrap();
+
+        assert_eq!(shallow.1.remaining_pieces.len(), 1);
+        assert!(deep.1.remaining_pieces.is_empty());
+        assert!(shallow.0 > deep.0);
+
+        let best = parallel::best_answer(vec![Some(shallow), None, Some(deep.clone())]).unwrap();
+        assert_eq!(best.0, deep.0);
+        assert!(Arc::ptr_eq(&best.1, &deep.1));
+    }
+
+    #[test]
+    fn test_transposition_table() {
+        let mut table = TranspositionTable::new();
+        let key = 0xDEAD_BEEF_0000_0001;
+
+        assert_eq!(table.probe(key), None);
+        assert!(table.improve(key, 10, 2));
+        assert_eq!(table.probe(key), Some((10, 2)));
+
+        // only a better evaluation of the same position is worth searching again
+        assert!(!table.improve(key, 10, 2));
+        assert!(table.improve(key, 11, 2));
+
+        // a shallower position landing in the same slot doesn't push out a deeper one
+        let same_slot = key ^ 1;
+        table.store(same_slot, 50, 1);
+        assert_eq!(table.probe(same_slot), None);
+        assert_eq!(table.probe(key), Some((11, 2)));
+
+        table.store(same_slot, 50, 3);
+        assert_eq!(table.probe(same_slot), Some((50, 3)));
+        assert_eq!(table.probe(key), None);
+    }
+
+    #[test]
+    fn test_ranking() {
+        let pieces = pieces::PieceSet::pentominoes();
+        let mut id_manager = game::IdManager::default();
+        let config = SearchConfig::default();
+
+        let mut bot = SearchBot::new(pieces.clone(), config).with_ranking(4);
+
+        let mut state = game::State::new(2, game::GameConfig::default());
+        state.remaining_pieces = vec![1, 8];
+
+        let solution = bot.choose(&state, &mut id_manager).unwrap();
+        let ranking = bot.ranking();
+
+        assert_eq!(ranking.len(), 4);
+        // the placement played comes first, then the others best first
+        assert!(ranking[0].played);
+        assert_eq!(ranking[0].placement, solution);
+        assert!(ranking[1..].iter().all(|placement| !placement.played));
+        assert!(ranking[1..]
+            .windows(2)
+            .all(|pair| pair[0].value >= pair[1].value));
+
+        for placement in ranking {
+            assert_eq!(placement.symbol, 'I');
+            assert_eq!(placement.placement.piece_id, 1);
+            assert_eq!(
+                placement
+                    .field
+                    .occupancy()
+                    .iter()
+                    .map(|mask| mask.count_ones())
+                    .sum::<u32>(),
+                5
+            );
+
+            // the breakdown adds up to the placement's own evaluation, its value
+            let total: f64 = placement.contributions.iter().map(|(_, share)| share).sum();
+            assert!((total - config.weights.evaluate(&placement.features)).abs() < 1e-9);
+            assert!((total - placement.value).abs() <= 1.0 / heuristic::PRIORITY_SCALE);
+        }
+
+        // no ranking unless asked for
+        let mut bot = SearchBot::new(pieces.clone(), config);
+        bot.choose(&state, &mut id_manager).unwrap();
+        assert!(bot.ranking().is_empty());
+
+        // with hold, only placements of the piece played are ranked
+        let mut state = i_well_state(2);
+        state.config.hold = true;
+        state.remaining_pieces = vec![pieces.id_of('X').unwrap(), pieces.id_of('I').unwrap()];
+
+        let mut bot = SearchBot::new(pieces.clone(), config).with_ranking(usize::MAX);
+        let solution = bot.choose(&state, &mut id_manager).unwrap();
+        assert!(solution.hold);
+
+        let ranking = bot.ranking();
+        assert!(ranking.len() > 1);
+        assert!(ranking
+            .iter()
+            .all(|placement| placement.placement.piece_id == solution.piece_id));
+    }
 }
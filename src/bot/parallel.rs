@@ -0,0 +1,74 @@
+use crate::bot::{answer_key, search_from, SearchConfig, SearchStats};
+use crate::{game, pieces};
+
+use std::sync::Arc;
+
+// deals the first placements out to `threads` workers, each running the configured
+// strategy below its share of them, and answers with the best of their answers
+pub fn search(
+    mut first_generation: Vec<(i32, game::State)>,
+    pieces: &pieces::PieceSet,
+    id_manager: &game::IdManager,
+    threads: usize,
+    config: &SearchConfig,
+    stats: &mut SearchStats,
+) -> Option<(i32, Arc<game::State>)> {
+    // most promising first, so every worker gets its fair share of them
+    first_generation.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    let mut shares: Vec<Vec<(i32, game::State)>> = (0..threads).map(|_| Vec::new()).collect();
+    for (i, child) in first_generation.into_iter().enumerate() {
+        shares[i % threads].push(child);
+    }
+
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = shares
+            .into_iter()
+            .filter(|share| !share.is_empty())
+            .map(|share| {
+                // ids only have to tell apart the pieces of one field, and the fields of
+                // a worker only ever hold its own placements on top of the initial state
+                let mut id_manager = id_manager.clone();
+                let mut worker_stats = stats.for_worker();
+
+                scope.spawn(move || {
+                    let answer =
+                        search_from(share, pieces, &mut id_manager, config, &mut worker_stats);
+                    (answer, worker_stats)
+                })
+            })
+            .collect();
+
+        let answers: Vec<_> = workers
+            .into_iter()
+            .map(|worker| {
+                let (answer, worker_stats) = worker.join().expect("search thread panicked");
+                stats.merge(&worker_stats);
+                answer
+            })
+            .collect();
+
+        best_answer(answers)
+    })
+}
+
+// the best of the workers' answers, compared like a single search compares its states,
+// see `answer_key()`, as a worker may run out of budget before reaching the full depth
+// ties go to the earlier worker, so the answer doesn't depend on timing
+pub(super) fn best_answer(
+    answers: Vec<Option<(i32, Arc<game::State>)>>,
+) -> Option<(i32, Arc<game::State>)> {
+    let mut best: Option<(i32, Arc<game::State>)> = None;
+
+    for (value, state) in answers.into_iter().flatten() {
+        let is_better = best.as_ref().is_none_or(|(best, best_state)| {
+            answer_key(value, &state) > answer_key(*best, best_state)
+        });
+
+        if is_better {
+            best = Some((value, state));
+        }
+    }
+
+    best
+}
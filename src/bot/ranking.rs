@@ -0,0 +1,81 @@
+use crate::bot::heuristic::{self, Features, FEATURE_COUNT};
+use crate::bot::Weights;
+use crate::{game, pieces};
+
+// a first placement, as judged by the search that chose the placement played
+#[derive(Clone, Debug)]
+pub struct RankedPlacement {
+    pub placement: game::Placement,
+    // the one the search chose
+    pub played: bool,
+    // after the placement, before clearing rows
+    pub field: game::GameField,
+    pub symbol: char,
+    // of the placement itself, after clearing rows, in the heuristic's units,
+    // see `Weights::evaluate()`
+    pub value: f64,
+    // of the placement itself, after clearing rows
+    pub features: Features,
+    // each feature's share of the placement's own evaluation
+    pub contributions: [(&'static str, f64); FEATURE_COUNT],
+}
+
+// the placement `played`, then the best of the other first placements of the same piece,
+// `k` at most, from the first generation of the search that chose it
+// with hold, placements of the piece it would swap in are left out
+// only the search's answer looks past the first piece, the others are valued on their own
+pub fn rank_placements(
+    initial_state: &game::State,
+    first_generation: &[(i32, game::State)],
+    played: Option<&game::Placement>,
+    pieces: &pieces::PieceSet,
+    weights: &Weights,
+    k: usize,
+) -> Vec<RankedPlacement> {
+    let Some(played) = played else {
+        return Vec::new();
+    };
+
+    let level = initial_state.level() as u64;
+
+    let mut ranking: Vec<RankedPlacement> = first_generation
+        .iter()
+        .filter_map(|(heuristic, child)| {
+            let placement = child.placement.clone()?;
+
+            if placement.piece_id != played.piece_id {
+                return None;
+            }
+
+            // first generation fields have their rows cleared already
+            let mut field = initial_state.field.clone();
+            field.place(
+                &pieces.moves(placement.piece_id).shapes[placement.permutation],
+                game::create_composite_id(placement.piece_id, 0),
+                placement.row,
+                placement.col,
+            );
+
+            // as measured by `heuristic::clear()`
+            let features = Features::new(
+                &child.field,
+                child.cleared_rows - initial_state.cleared_rows,
+                (child.score - initial_state.score) / level,
+            );
+
+            Some(RankedPlacement {
+                played: placement == *played,
+                symbol: pieces.symbol(placement.piece_id),
+                placement,
+                field,
+                value: *heuristic as f64 / heuristic::PRIORITY_SCALE,
+                features,
+                contributions: weights.contributions(&features),
+            })
+        })
+        .collect();
+
+    ranking.sort_by(|a, b| b.played.cmp(&a.played).then(b.value.total_cmp(&a.value)));
+    ranking.truncate(k);
+    ranking
+}
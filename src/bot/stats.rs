@@ -0,0 +1,140 @@
+use crate::game;
+
+use std::time::{Duration, Instant};
+
+// what a search spent its time on, see `bot::search()`
+// counters add up across threads, so times are thread time rather than wall time
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchStats {
+    // searches merged into these stats, see `merge()`
+    pub searches: u32,
+    // states whose placements of the next piece were generated, rollouts included
+    pub nodes_expanded: u64,
+    pub states_generated: u64,
+    // generated states skipped as already reached at least as well, see `TranspositionTable`
+    pub transposition_hits: u64,
+    // largest priority queue, beam (before truncating) or tree of a search
+    pub max_frontier: usize,
+    // time spent expanding states, by how many pieces were placed before them
+    pub time_per_depth: Vec<Duration>,
+    pub total_time: Duration,
+    // pieces in the preview of the searched state, to tell the depth of expanded states
+    preview_size: usize,
+    // the `record_*()` methods do nothing otherwise, see `SearchConfig::stats`
+    recording: bool,
+}
+
+impl SearchStats {
+    pub fn new(initial_state: &game::State, recording: bool) -> Self {
+        SearchStats {
+            searches: 1,
+            preview_size: initial_state.remaining_pieces.len(),
+            recording,
+            ..SearchStats::default()
+        }
+    }
+
+    pub(super) fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    // average placements per expanded state
+    pub fn branching_factor(&self) -> f64 {
+        self.states_generated as f64 / self.nodes_expanded.max(1) as f64
+    }
+
+    // for a thread searching part of the same search, merged back into it afterwards
+    pub(super) fn for_worker(&self) -> Self {
+        SearchStats {
+            searches: 0,
+            preview_size: self.preview_size,
+            recording: self.recording,
+            ..SearchStats::default()
+        }
+    }
+
+    pub(super) fn record_expansion(
+        &mut self,
+        state: &game::State,
+        generated: usize,
+        start: Instant,
+    ) {
+        if !self.recording {
+            return;
+        }
+
+        let depth = self
+            .preview_size
+            .saturating_sub(state.remaining_pieces.len());
+
+        if self.time_per_depth.len() <= depth {
+            self.time_per_depth.resize(depth + 1, Duration::ZERO);
+        }
+        self.time_per_depth[depth] += start.elapsed();
+
+        self.nodes_expanded += 1;
+        self.states_generated += generated as u64;
+    }
+
+    pub(super) fn record_frontier(&mut self, size: usize) {
+        if self.recording {
+            self.max_frontier = self.max_frontier.max(size);
+        }
+    }
+
+    pub(super) fn record_transposition_hits(&mut self, hits: usize) {
+        if self.recording {
+            self.transposition_hits += hits as u64;
+        }
+    }
+
+    // totals of both, the largest frontier of either
+    pub fn merge(&mut self, other: &SearchStats) {
+        self.searches += other.searches;
+        self.nodes_expanded += other.nodes_expanded;
+        self.states_generated += other.states_generated;
+        self.transposition_hits += other.transposition_hits;
+        self.max_frontier = self.max_frontier.max(other.max_frontier);
+        self.total_time += other.total_time;
+        self.preview_size = self.preview_size.max(other.preview_size);
+
+        if self.time_per_depth.len() < other.time_per_depth.len() {
+            self.time_per_depth
+                .resize(other.time_per_depth.len(), Duration::ZERO);
+        }
+        for (total, time) in self.time_per_depth.iter_mut().zip(&other.time_per_depth) {
+            *total += *time;
+        }
+    }
+}
+
+// averaged per search
+impl std::fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let searches = self.searches.max(1);
+
+        writeln!(
+            f,
+            "nodes expanded: {:.1}",
+            self.nodes_expanded as f64 / searches as f64
+        )?;
+        writeln!(
+            f,
+            "states generated: {:.1}",
+            self.states_generated as f64 / searches as f64
+        )?;
+        writeln!(f, "branching factor: {:.2}", self.branching_factor())?;
+        writeln!(
+            f,
+            "transposition hits: {:.1}",
+            self.transposition_hits as f64 / searches as f64
+        )?;
+        writeln!(f, "max frontier: {}", self.max_frontier)?;
+
+        for (depth, time) in self.time_per_depth.iter().enumerate() {
+            writeln!(f, "time at depth {}: {:?}", depth, *time / searches)?;
+        }
+
+        write!(f, "search time: {:?}", self.total_time / searches)
+    }
+}
@@ -0,0 +1,66 @@
+// positions seen by a search, keyed by their Zobrist hash (`State::zobrist()`),
+// with the best evaluation found for them and their depth, the pieces left to place
+// a fixed number of slots with one entry each, so memory stays bounded however long
+// the search runs, a slot keeps the deeper of two positions as it saves more work
+// slots are zeroed memory, only touched once written to, so a search that sees a
+// few thousand positions doesn't pay for the whole table
+pub struct TranspositionTable {
+    keys: Vec<u64>,
+    values: Vec<i32>,
+    depths: Vec<u8>,
+}
+
+// 2^16 slots, 832 KiB
+const TABLE_BITS: u32 = 16;
+
+impl TranspositionTable {
+    pub fn new() -> Self {
+        let size = 1 << TABLE_BITS;
+
+        TranspositionTable {
+            keys: vec![0; size],
+            values: vec![0; size],
+            depths: vec![0; size],
+        }
+    }
+
+    fn slot(key: u64) -> usize {
+        // the high bits, the low ones of neighbouring positions are more alike
+        (key >> (u64::BITS - TABLE_BITS)) as usize
+    }
+
+    // the evaluation and depth stored for `key`, if it is still in the table
+    pub fn probe(&self, key: u64) -> Option<(i32, u8)> {
+        let slot = Self::slot(key);
+
+        // 0 marks an empty slot, a position that hashes to 0 is just never found
+        match self.keys[slot] == key && key != 0 {
+            true => Some((self.values[slot], self.depths[slot])),
+            false => None,
+        }
+    }
+
+    pub fn store(&mut self, key: u64, value: i32, depth: u8) {
+        let slot = Self::slot(key);
+
+        if self.keys[slot] != key && self.depths[slot] > depth {
+            return;
+        }
+
+        self.keys[slot] = key;
+        self.values[slot] = value;
+        self.depths[slot] = depth;
+    }
+
+    // stores `value` unless the table already has the position with a value at least
+    // as good, returns whether it did, i.e. whether the position is worth searching
+    pub fn improve(&mut self, key: u64, value: i32, depth: u8) -> bool {
+        match self.probe(key) {
+            Some((stored, _)) if stored >= value => false,
+            _ => {
+                self.store(key, value, depth);
+                true
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::game::field::MAX_FIELD_WIDTH;
+use crate::game::scoring::ScoringRules;
+
+use std::str::FromStr;
+
+pub const DEFAULT_FIELD_WIDTH: usize = 5;
+pub const DEFAULT_FIELD_HEIGHT: usize = 15;
+
+// runtime game settings, carried by every `State` so the bot, gravity,
+// clearing and rendering all agree on the board they are working with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GameConfig {
+    pub width: usize,
+    pub height: usize,
+    pub scoring: ScoringRules,
+    // whether the current piece can be swapped with the held one before placing it,
+    // see `State::held_piece`
+    pub hold: bool,
+    // survival mode, a garbage row is pushed up after every this many placed pieces,
+    // 0 for none, see `game::garbage`
+    pub garbage_interval: u32,
+    pub gravity: GravityRule,
+}
+
+impl GameConfig {
+    pub fn new(width: usize, height: usize) -> Self {
+        GameConfig {
+            width,
+            height,
+            scoring: ScoringRules::default(),
+            hold: false,
+            garbage_interval: 0,
+            gravity: GravityRule::default(),
+        }
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig::new(DEFAULT_FIELD_WIDTH, DEFAULT_FIELD_HEIGHT)
+    }
+}
+
+// parses board sizes given as "<width>x<height>", e.g. "10x20"
+impl FromStr for GameConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = s.split_once('x').ok_or(format!(
+            "invalid board size '{}', expected <width>x<height>",
+            s
+        ))?;
+
+        let width: usize = width
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid board width '{}'", width))?;
+        let height: usize = height
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid board height '{}'", height))?;
+
+        if width == 0 || height == 0 {
+            return Err(format!(
+                "board size must be non-zero, got {}x{}",
+                width, height
+            ));
+        }
+
+        // rows are bitmasks, see `GameField`
+        if width > MAX_FIELD_WIDTH {
+            return Err(format!(
+                "board width must be at most {}, got {}",
+                MAX_FIELD_WIDTH, width
+            ));
+        }
+
+        Ok(GameConfig::new(width, height))
+    }
+}
+
+// what happens to the tiles above once full rows are cleared, see `game::update()`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GravityRule {
+    // classic Tetris, the rows above drop by exactly the number of rows cleared below them,
+    // leaving holes and overhangs as they were
+    Naive,
+    // every piece, or piece fragment split up by a clear, falls as a whole until it
+    // rests on something, which can fill more rows
+    #[default]
+    Sticky,
+    // every tile falls on its own to the bottom of its column, which can fill more rows
+    FreeFall,
+}
+
+// parses "naive", "sticky" or "free-fall"
+impl FromStr for GravityRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "naive" => Ok(GravityRule::Naive),
+            "sticky" => Ok(GravityRule::Sticky),
+            "free-fall" => Ok(GravityRule::FreeFall),
+            _ => Err(format!(
+                "unknown gravity rule '{}', expected naive, sticky or free-fall",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for GravityRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GravityRule::Naive => write!(f, "naive"),
+            GravityRule::Sticky => write!(f, "sticky"),
+            GravityRule::FreeFall => write!(f, "free-fall"),
+        }
+    }
+}
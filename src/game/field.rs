@@ -0,0 +1,224 @@
+use crate::game::{self, zobrist};
+
+// widest board a row bitmask can hold
+pub const MAX_FIELD_WIDTH: usize = u32::BITS as usize;
+
+// occupancy is kept as one bitmask per row (bit `col` set when occupied), which is all
+// the search hot path needs for overlap, support and full-row checks
+// composite ids are kept in a flat side buffer, only read by gravity, the
+// connectivity check and rendering
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GameField {
+    width: usize,
+    height: usize,
+    occupancy: Vec<u32>,
+    tiles: Vec<u32>,
+    // Zobrist hash of `occupancy`, kept up to date by every change to it
+    zobrist: u64,
+}
+
+impl GameField {
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!(
+            width <= MAX_FIELD_WIDTH,
+            "board width {} exceeds maximum of {}",
+            width,
+            MAX_FIELD_WIDTH
+        );
+
+        GameField {
+            width,
+            height,
+            occupancy: vec![0; height],
+            tiles: vec![game::EMPTY; width * height],
+            zobrist: 0,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> u32 {
+        self.tiles[row * self.width + col]
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, tile: u32) {
+        self.tiles[row * self.width + col] = tile;
+
+        let was_empty = self.is_empty(row, col);
+
+        if tile == game::EMPTY {
+            self.occupancy[row] &= !(1 << col);
+        } else {
+            self.occupancy[row] |= 1 << col;
+        }
+
+        if was_empty != (tile == game::EMPTY) {
+            self.zobrist ^= zobrist::cell_key(row, col);
+        }
+    }
+
+    pub fn is_empty(&self, row: usize, col: usize) -> bool {
+        self.occupancy[row] & (1 << col) == 0
+    }
+
+    pub fn occupancy(&self) -> &[u32] {
+        &self.occupancy
+    }
+
+    // see `game::zobrist`
+    pub fn zobrist(&self) -> u64 {
+        self.zobrist
+    }
+
+    // composite ids, row by row from the top
+    pub fn rows(&self) -> impl Iterator<Item = &[u32]> {
+        self.tiles.chunks(self.width)
+    }
+
+    // bitmask with all `width` bits set
+    pub fn full_mask(&self) -> u32 {
+        if self.width == MAX_FIELD_WIDTH {
+            u32::MAX
+        } else {
+            (1 << self.width) - 1
+        }
+    }
+
+    pub fn is_row_full(&self, row: usize) -> bool {
+        self.occupancy[row] == self.full_mask()
+    }
+
+    pub fn has_full_row(&self) -> bool {
+        self.occupancy.contains(&self.full_mask())
+    }
+
+    pub fn clear_row(&mut self, row: usize) {
+        self.zobrist ^= zobrist::row_key(row, self.occupancy[row]);
+        self.occupancy[row] = 0;
+        self.tiles[row * self.width..(row + 1) * self.width].fill(game::EMPTY);
+    }
+
+    // moves every row up one, the top row falling off, and fills the new bottom row
+    // with `composite_id` but for the `gap` column
+    // returns false if the top row had any tile, which are lost
+    pub fn push_bottom_row(&mut self, gap: usize, composite_id: u32) -> bool {
+        let fits = self.occupancy[0] == 0;
+
+        self.occupancy.remove(0);
+        self.occupancy.push(self.full_mask() & !(1 << gap));
+
+        self.tiles.drain(..self.width);
+        self.tiles
+            .extend((0..self.width).map(|col| match col == gap {
+                true => game::EMPTY,
+                false => composite_id,
+            }));
+
+        self.recompute_zobrist();
+
+        fits
+    }
+
+    // takes `row` out, every row above moves down one and an empty row comes in at the top
+    pub fn remove_row(&mut self, row: usize) {
+        self.occupancy.remove(row);
+        self.occupancy.insert(0, 0);
+
+        self.tiles.drain(row * self.width..(row + 1) * self.width);
+        self.tiles
+            .splice(0..0, std::iter::repeat_n(game::EMPTY, self.width));
+
+        self.recompute_zobrist();
+    }
+
+    // for when rows move, which changes the key of every tile in them
+    fn recompute_zobrist(&mut self) {
+        self.zobrist = self
+            .occupancy
+            .iter()
+            .enumerate()
+            .fold(0, |hash, (row, &mask)| hash ^ zobrist::row_key(row, mask));
+    }
+
+    // true if `shape` is in bounds and does not overlap any tile,
+    // with its top-left corner at [row][col]
+    pub fn fits(&self, shape: &ShapeMask, row: usize, col: usize) -> bool {
+        if row + shape.height > self.height || col + shape.width > self.width {
+            return false;
+        }
+
+        shape
+            .rows
+            .iter()
+            .enumerate()
+            .all(|(delta_row, &mask)| self.occupancy[row + delta_row] & (mask << col) == 0)
+    }
+
+    // does not check for overlaps, see `fits()`
+    pub fn place(&mut self, shape: &ShapeMask, composite_id: u32, row: usize, col: usize) {
+        for (delta_row, &mask) in shape.rows.iter().enumerate() {
+            let tile_row = row + delta_row;
+            self.occupancy[tile_row] |= mask << col;
+            self.zobrist ^= zobrist::row_key(tile_row, mask << col);
+
+            for delta_col in 0..shape.width {
+                if mask & (1 << delta_col) != 0 {
+                    self.tiles[tile_row * self.width + col + delta_col] = composite_id;
+                }
+            }
+        }
+    }
+}
+
+// mostly for building fields by hand in tests
+impl From<Vec<Vec<u32>>> for GameField {
+    fn from(rows: Vec<Vec<u32>>) -> Self {
+        let height = rows.len();
+        let width = rows.first().map_or(0, |row| row.len());
+
+        let mut field = GameField::new(width, height);
+
+        for (row, tiles) in rows.iter().enumerate() {
+            for (col, &tile) in tiles.iter().enumerate() {
+                field.set(row, col, tile);
+            }
+        }
+
+        field
+    }
+}
+
+// a piece permutation as one bitmask per row, bit `col` set when the shape covers it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShapeMask {
+    pub rows: Vec<u32>,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl ShapeMask {
+    pub fn new(shape: &[Vec<u8>]) -> Self {
+        let rows = shape
+            .iter()
+            .map(|shape_row| {
+                shape_row
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &cell)| cell != 0)
+                    .fold(0, |mask, (col, _)| mask | (1 << col))
+            })
+            .collect();
+
+        ShapeMask {
+            rows,
+            width: shape.first().map_or(0, |row| row.len()),
+            height: shape.len(),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use crate::game::{self, GameStatus, IdManager, State};
+
+use rand::rngs::StdRng;
+use rand::Rng;
+
+// the piece id of garbage tiles, past any piece set, see `pieces::PieceSet`
+pub const GARBAGE_PIECE_ID: u8 = u8::MAX;
+
+// survival mode: once every `config.garbage_interval` placed pieces, pushes a garbage
+// row up from the bottom, full but for a random gap
+// each row is a single piece to clearing and gravity, so it only splits up or falls
+// like any placed piece would
+// tiles pushed off the top end the game, returns whether a row was pushed
+pub fn push_if_due(state: &mut State, rng: &mut StdRng, id_manager: &mut IdManager) -> bool {
+    let interval = state.config.garbage_interval;
+
+    if interval == 0
+        || state.pieces_placed == 0
+        || !state.pieces_placed.is_multiple_of(interval)
+        || state.status != GameStatus::Playing
+    {
+        return false;
+    }
+
+    let gap = rng.gen_range(0..state.field.width());
+    let composite_id = game::create_composite_id(GARBAGE_PIECE_ID, id_manager.next_unique_id());
+
+    if !state.field.push_bottom_row(gap, composite_id) {
+        state.status = GameStatus::GameOver;
+    }
+
+    true
+}
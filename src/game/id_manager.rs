@@ -1,28 +1,23 @@
+// unique ids only have to tell apart pieces currently on the board,
+// a wrapping 24 bit counter leaves plenty of room before an id comes around again
+const MAX_UNIQUE_ID: u32 = 0x00FF_FFFF;
+
+#[derive(Clone)]
 pub struct IdManager {
-    used_ids: Vec<bool>,
+    next_id: u32,
 }
 
 impl IdManager {
     pub fn default() -> Self {
-        Self {
-            used_ids: vec![false; 4096],
-        }
+        Self { next_id: 0 }
     }
 
-    pub fn next_unique_id(&mut self, pent_id: u8) -> u16 {
-        // next_id = pent_id + multiple of 12
-        let mut next_id = pent_id as usize;
+    pub fn next_unique_id(&mut self) -> u32 {
+        let id = self.next_id;
+
+        // MAX_UNIQUE_ID itself is never handed out, as it is part of `game::EMPTY`
+        self.next_id = (self.next_id + 1) % MAX_UNIQUE_ID;
 
-        loop {
-            // if next_id is larger than 12 bits
-            if (next_id & 0xF000) != 0 {
-                self.used_ids.fill(false);
-                return pent_id as u16;
-            } else if !self.used_ids[next_id] {
-                self.used_ids[next_id] = true;
-                return next_id as u16;
-            }
-            next_id += 12;
-        }
+        id
     }
 }
@@ -1,110 +1,145 @@
+mod config;
+mod field;
+pub mod garbage;
 mod id_manager;
+mod movement;
 mod next_shapes;
+mod placement;
+pub mod randomizer;
+pub mod scoring;
 mod state;
+mod zobrist;
 
 // re-export modules to import with game::State instead of game::state::State
 pub use crate::game::{
-    id_manager::IdManager, next_shapes::NextShapes, state::GameField, state::State,
+    config::GameConfig, config::GravityRule, field::GameField, field::ShapeMask,
+    id_manager::IdManager, movement::PieceMoves, next_shapes::NextShapes,
+    placement::apply_placement, placement::Placement, randomizer::Randomizer, state::GameStatus,
+    state::State,
 };
 
+use crate::pieces::PieceSet;
+
 use std::collections::{HashSet, VecDeque};
 
-pub const FIELD_WIDTH: usize = 5;
-pub const FIELD_HEIGHT: usize = 15;
-pub const EMPTY: u16 = 13;
-pub const STACK_SIZE: usize = 4;
+pub const EMPTY: u32 = u32::MAX;
+
+// block out: the next piece has nowhere to spawn, which ends the game
+pub fn is_blocked_out(state: &State, pieces: &PieceSet) -> bool {
+    match state.remaining_pieces.first() {
+        Some(&piece_id) => pieces
+            .moves(piece_id)
+            .spawn_position(&state.field)
+            .is_none(),
+        None => false,
+    }
+}
+
+// clears full rows and applies gravity until no full row is left, then scores the placement
+// gravity follows `state.config.gravity`
+// returns the number of cleared rows
+pub fn update(state: &mut State, id_manager: &mut IdManager) -> u32 {
+    let mut passes = Vec::new();
+
+    clear_rows(
+        &mut state.field,
+        state.config.gravity,
+        id_manager,
+        &mut passes,
+    );
+    scoring::record_clears(state, &passes);
+
+    passes.iter().sum()
+}
 
-// recursively clears full rows and applies gravity
-pub fn update(
-    state: &mut State,
+// recursively clears full rows and applies gravity,
+// pushing the number of rows cleared on each pass (recursion) to `passes`
+fn clear_rows(
+    field: &mut GameField,
+    rule: GravityRule,
     id_manager: &mut IdManager,
-    mut cleared_count: u32,
-    mut clear_rows: bool,
-) -> u32 {
+    passes: &mut Vec<u32>,
+) {
     // base case
-    if !clear_rows {
-        return cleared_count;
+    // placed pieces always rest on something, so there is nothing to
+    // relabel or drop unless a row is about to be cleared
+    if !field.has_full_row() {
+        return;
     }
 
-    clear_rows = false;
+    let mut cleared = Vec::new();
 
     // for each row, we either clear it or update the composite_id of its tiles
     // rev() to start from the bottom
-    for row in (0..FIELD_HEIGHT).rev() {
-        // all() is short-circuiting
-        // if row is full
-        if state.field[row].iter().all(|&x| x != EMPTY) {
-            // clear row
-            for col in 0..FIELD_WIDTH {
-                state.field[row][col] = EMPTY;
-            }
+    for row in (0..field.height()).rev() {
+        if field.is_row_full(row) {
+            field.clear_row(row);
 
-            cleared_count += 1;
-            state.cleared_rows += 1;
+            cleared.push(row);
 
-            clear_rows = true;
+            continue;
+        }
 
+        // only sticky gravity moves tiles by piece
+        if rule != GravityRule::Sticky {
             continue;
         }
 
         // update composite_id of separated tiles
-        for col in 0..FIELD_WIDTH {
-            let tile = state.field[row][col];
+        for col in 0..field.width() {
+            let tile = field.get(row, col);
 
-            if tile == EMPTY
-                || is_connected(&state.field, row as u8, col as u8, &get_unique_id(tile))
-            {
+            if tile == EMPTY || is_connected(field, row as u8, col as u8, &get_unique_id(tile)) {
                 continue;
             }
 
-            let pent_id = get_pent_id(tile);
+            let piece_id = get_piece_id(tile);
 
-            state.field[row][col] =
-                create_composite_id(pent_id, id_manager.next_unique_id(pent_id));
+            field.set(
+                row,
+                col,
+                create_composite_id(piece_id, id_manager.next_unique_id()),
+            );
         }
     }
 
-    gravity(&mut state.field);
+    passes.push(cleared.len() as u32);
 
-    update(state, id_manager, cleared_count, clear_rows);
+    gravity(field, rule, &cleared);
 
-    cleared_count
+    clear_rows(field, rule, id_manager, passes);
 }
 
 // animated version of update():
 // applies game logic and also
 // populates a vector of `GameField`s to be drawn, as animation frames
+// rows cleared on each pass are pushed to `passes`, to be scored with `scoring::record_clears()`
 pub fn animate_update(
     field: &mut GameField,
+    rule: GravityRule,
     id_manager: &mut id_manager::IdManager,
     mut continue_update: bool,
-    cleared_count: u32,
-    total_cleared_count: &mut u32,
+    passes: &mut Vec<u32>,
     frames: &mut VecDeque<GameField>,
 ) {
     if !continue_update {
         return;
     }
 
-    // TODO refactor to not depend on cleared_count
     // initial frame, add before any updating
-    if cleared_count == 0 {
+    if frames.is_empty() {
         frames.push_back(field.clone());
     }
 
     continue_update = false;
+    let mut cleared = Vec::new();
 
     // for each row, we either clear it or update the composite_id of its tiles
-    for row in (0..FIELD_HEIGHT).rev() {
-        // all() is short-circuiting
-        // if row is full
-        if field[row].iter().all(|&x| x != EMPTY) {
-            // clear row
-            for col in 0..FIELD_WIDTH {
-                field[row][col] = EMPTY;
-            }
+    for row in (0..field.height()).rev() {
+        if field.is_row_full(row) {
+            field.clear_row(row);
 
-            *total_cleared_count += 1;
+            cleared.push(row);
             continue_update = true;
 
             frames.push_back(field.clone());
@@ -112,62 +147,101 @@ pub fn animate_update(
             continue;
         }
 
+        // only sticky gravity moves tiles by piece
+        if rule != GravityRule::Sticky {
+            continue;
+        }
+
         // update composite_id of separated tiles
-        for col in 0..FIELD_WIDTH {
-            let tile = field[row][col];
+        for col in 0..field.width() {
+            let tile = field.get(row, col);
 
             if tile == EMPTY || is_connected(field, row as u8, col as u8, &get_unique_id(tile)) {
                 continue;
             }
 
-            let pent_id = get_pent_id(tile);
+            let piece_id = get_piece_id(tile);
 
-            field[row][col] = create_composite_id(pent_id, id_manager.next_unique_id(pent_id));
+            field.set(
+                row,
+                col,
+                create_composite_id(piece_id, id_manager.next_unique_id()),
+            );
         }
     }
 
+    if !cleared.is_empty() {
+        passes.push(cleared.len() as u32);
+    }
+
     frames.push_back(field.clone());
 
-    gravity(field);
+    gravity(field, rule, &cleared);
 
     frames.push_back(field.clone());
 
-    animate_update(
-        field,
-        id_manager,
-        continue_update,
-        cleared_count,
-        total_cleared_count,
-        frames,
-    );
+    animate_update(field, rule, id_manager, continue_update, passes, frames);
 }
 
-fn gravity(field: &mut GameField) {
+// `cleared` are the rows just cleared, bottom first
+fn gravity(field: &mut GameField, rule: GravityRule, cleared: &[usize]) {
+    match rule {
+        GravityRule::Naive => {
+            // top first, so the rows left to remove stay where they are
+            for &row in cleared.iter().rev() {
+                field.remove_row(row);
+            }
+        }
+        GravityRule::Sticky => sticky_gravity(field),
+        GravityRule::FreeFall => {
+            for col in 0..field.width() {
+                let mut landing_row = field.height();
+
+                for row in (0..field.height()).rev() {
+                    if field.is_empty(row, col) {
+                        continue;
+                    }
+
+                    landing_row -= 1;
+
+                    if row != landing_row {
+                        field.set(landing_row, col, field.get(row, col));
+                        field.set(row, col, EMPTY);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn sticky_gravity(field: &mut GameField) {
     // if one tile is settled, so will the rest of the tiles that make up the piece
     // where a tile is an entry in a 2d vec (game field),
     // tiles of the same piece have the same composite_id
 
-    let mut settled_ids: HashSet<u16> = HashSet::new();
+    let height = field.height();
+
+    let mut settled_ids: HashSet<u32> = HashSet::new();
     let mut possible_shifts: Vec<(usize, usize)> = Vec::new();
 
     loop {
         settled_ids.clear();
         possible_shifts.clear();
 
-        for row in (0..FIELD_HEIGHT).rev() {
-            for col in 0..FIELD_WIDTH {
-                let tile = field[row][col];
-
-                if tile == EMPTY {
+        for row in (0..height).rev() {
+            for col in 0..field.width() {
+                if field.is_empty(row, col) {
                     continue;
                 }
 
-                if row == (FIELD_HEIGHT - 1) {
+                let tile = field.get(row, col);
+
+                if row == (height - 1) {
                     settled_ids.insert(tile);
                     continue;
                 }
 
-                let below = field[row + 1][col];
+                let below = field.get(row + 1, col);
 
                 if below != EMPTY && below != tile {
                     settled_ids.insert(tile);
@@ -181,14 +255,14 @@ fn gravity(field: &mut GameField) {
         let mut shifted = false;
 
         for &(row, col) in &possible_shifts {
-            let tile = field[row][col];
+            let tile = field.get(row, col);
 
             if settled_ids.contains(&tile) {
                 continue;
             }
 
-            field[row][col] = EMPTY;
-            field[row + 1][col] = tile;
+            field.set(row, col, EMPTY);
+            field.set(row + 1, col, tile);
 
             shifted = true;
         }
@@ -200,23 +274,23 @@ fn gravity(field: &mut GameField) {
 }
 
 // checks if tile is connected to other tiles of the same piece
-fn is_connected(field: &GameField, row: u8, col: u8, unique_id: &u16) -> bool {
+fn is_connected(field: &GameField, row: u8, col: u8, unique_id: &u32) -> bool {
     // neighbor offsets
-    let deltas = vec![(-1, 0), (0, 1), (1, 0), (0, -1)];
+    let deltas = [(-1, 0), (0, 1), (1, 0), (0, -1)];
 
     for (delta_row, delta_col) in deltas {
         let tile_row = row as i8 + delta_row;
         let tile_col = col as i8 + delta_col;
 
         if tile_row < 0
-            || tile_row >= field.len() as i8
+            || tile_row >= field.height() as i8
             || tile_col < 0
-            || tile_col >= field[0].len() as i8
+            || tile_col >= field.width() as i8
         {
             continue;
         }
 
-        let neighbor = field[tile_row as usize][tile_col as usize];
+        let neighbor = field.get(tile_row as usize, tile_col as usize);
 
         if neighbor != EMPTY && get_unique_id(neighbor) == *unique_id {
             return true;
@@ -226,17 +300,17 @@ fn is_connected(field: &GameField, row: u8, col: u8, unique_id: &u16) -> bool {
     false
 }
 
-// composite_id (16 bits) = pent_id (4 bits) + unique_id (12 bits)
-pub fn create_composite_id(pent_id: u8, unique_id: u16) -> u16 {
-    ((pent_id as u16) << 12) | (unique_id & 0x0FFF) // extract 12 bits
+// composite_id (32 bits) = piece_id (8 bits) + unique_id (24 bits)
+pub fn create_composite_id(piece_id: u8, unique_id: u32) -> u32 {
+    ((piece_id as u32) << 24) | (unique_id & 0x00FF_FFFF) // extract 24 bits
 }
 
-pub fn get_pent_id(composite_id: u16) -> u8 {
-    (composite_id >> 12) as u8
+pub fn get_piece_id(composite_id: u32) -> u8 {
+    (composite_id >> 24) as u8
 }
 
-pub fn get_unique_id(composite_id: u16) -> u16 {
-    composite_id & 0x0FFF
+pub fn get_unique_id(composite_id: u32) -> u32 {
+    composite_id & 0x00FF_FFFF
 }
 
 #[cfg(test)]
@@ -249,18 +323,20 @@ mod tests {
 
     #[test]
     fn test_gravity() {
-        let mut state = State::new(crate::DEFAULT_LOOKAHEAD_SIZE);
-        state.remaining_pieces = vec!['X'];
+        let mut state = State::new(crate::DEFAULT_LOOKAHEAD_SIZE, GameConfig::default());
+        state.remaining_pieces = vec![0];
+
+        let pieces = PieceSet::pentominoes();
 
         let mut id_manager = IdManager::default();
 
-        let l_composite_id = create_composite_id(8, id_manager.next_unique_id(8));
+        let l_composite_id = create_composite_id(8, id_manager.next_unique_id());
 
-        let p_composite_id = create_composite_id(9, id_manager.next_unique_id(9));
+        let p_composite_id = create_composite_id(9, id_manager.next_unique_id());
 
-        let x_composite_id = create_composite_id(0, id_manager.next_unique_id(0));
+        let x_composite_id = create_composite_id(0, id_manager.next_unique_id());
 
-        let field1 = vec![
+        let _field1 = vec![
             vec![
                 game::EMPTY,
                 game::EMPTY,
@@ -368,7 +444,7 @@ mod tests {
             ],
         ];
 
-        let field2 = vec![
+        let _field2 = vec![
             vec![
                 game::EMPTY,
                 game::EMPTY,
@@ -584,21 +660,23 @@ mod tests {
             ],
         ];
 
-        state.field = field3;
+        state.field = field3.into();
 
-        println!("b4 clear+gravity\n {}", state);
+        println!("b4 clear+gravity\n {}", state.display(&pieces));
 
-        update(&mut state, &mut id_manager, 0, true);
+        update(&mut state, &mut id_manager);
 
-        println!("after clear+gravity\n {}", state);
+        println!("after clear+gravity\n {}", state.display(&pieces));
 
-        assert_eq!(state.field[2][0], EMPTY);
+        assert_eq!(state.field.get(2, 0), EMPTY);
     }
 
     #[test]
     fn test_update() {
-        let mut state = State::new(crate::DEFAULT_LOOKAHEAD_SIZE);
-        state.remaining_pieces = vec!['X'];
+        let mut state = State::new(crate::DEFAULT_LOOKAHEAD_SIZE, GameConfig::default());
+        state.remaining_pieces = vec![0];
+
+        let pieces = PieceSet::pentominoes();
 
         let comp_id1 = create_composite_id(9, 0);
         let comp_id2 = create_composite_id(11, 1);
@@ -693,20 +771,30 @@ mod tests {
             vec![comp_id1, game::EMPTY, game::EMPTY, game::EMPTY, game::EMPTY],
         ];
 
-        state.field = field;
+        state.field = field.into();
 
-        println!("b4 clear\n {}", state);
+        println!("b4 clear\n {}", state.display(&pieces));
         println!("P unique_id: {}", get_unique_id(comp_id1));
 
-        update(&mut state, &mut IdManager::default(), 0, true);
-        println!("after clear\n {}", state);
+        update(&mut state, &mut IdManager::default());
+        println!("after clear\n {}", state.display(&pieces));
 
-        // assert_eq!(state.field[13], vec![EMPTY; FIELD_WIDTH as usize]);
+        // assert_eq!(state.field.occupancy()[13], 0);
         // assert_eq!(state.field[12][0], EMPTY);
-        assert_eq!(
-            is_connected(&state.field, 12, 0, &get_unique_id(comp_id1)),
-            false
-        );
+        assert!(!is_connected(&state.field, 12, 0, &get_unique_id(comp_id1)));
+    }
+
+    #[test]
+    fn test_board_size() {
+        let config: GameConfig = "10x20".parse().unwrap();
+        assert_eq!((config.width, config.height), (10, 20));
+
+        assert!("0x20".parse::<GameConfig>().is_err());
+        assert!("10by20".parse::<GameConfig>().is_err());
+
+        // widest a row bitmask can hold
+        assert!("32x20".parse::<GameConfig>().is_ok());
+        assert!("40x20".parse::<GameConfig>().is_err());
     }
 
     #[test]
@@ -714,7 +802,7 @@ mod tests {
         for x in 0..12 {
             for y in 0..4096 {
                 let composite_id = create_composite_id(x, y);
-                assert_eq!(get_pent_id(composite_id), x);
+                assert_eq!(get_piece_id(composite_id), x);
                 assert_eq!(get_unique_id(composite_id), y);
             }
         }
@@ -724,7 +812,392 @@ mod tests {
     fn test_get_unique_id() {
         let composite_id = create_composite_id(13, 2);
 
-        assert_eq!(get_pent_id(composite_id), 13);
+        assert_eq!(get_piece_id(composite_id), 13);
         assert_eq!(get_unique_id(composite_id), 2);
     }
+
+    #[test]
+    fn test_seeded_next_shapes() {
+        let pieces = PieceSet::pentominoes();
+        let next_shapes = |seed| {
+            let randomizer = randomizer::load("bag", &pieces).unwrap();
+            NextShapes::new(crate::DEFAULT_LOOKAHEAD_SIZE, randomizer, seed)
+        };
+
+        let state = State::new(crate::DEFAULT_LOOKAHEAD_SIZE, GameConfig::default());
+
+        let mut a = next_shapes(42);
+        let mut b = next_shapes(42);
+
+        for _ in 0..50 {
+            assert_eq!(a.get_next_stack(&state), b.get_next_stack(&state));
+        }
+
+        let mut c = next_shapes(43);
+        let a_stacks: Vec<Vec<u8>> = (0..10).map(|_| a.get_next_stack(&state)).collect();
+        let c_stacks: Vec<Vec<u8>> = (0..10).map(|_| c.get_next_stack(&state)).collect();
+
+        assert_ne!(a_stacks, c_stacks);
+
+        // a skipped shape never comes first
+        let mut d = next_shapes(43);
+        let first = d.get_next_stack(&state);
+        d.skip();
+        assert_eq!(d.get_next_stack(&state)[0], first[2]);
+    }
+
+    #[test]
+    fn test_randomizers() {
+        use rand::Rng;
+
+        let pieces = PieceSet::pentominoes();
+        let state = State::new(crate::DEFAULT_LOOKAHEAD_SIZE, GameConfig::default());
+        let mut rng = rand::SeedableRng::seed_from_u64(0);
+
+        // every piece exactly once (twice) per bag
+        for (name, copies) in [("bag", 1), ("double-bag", 2)] {
+            let mut randomizer = randomizer::load(name, &pieces).unwrap();
+
+            for _ in 0..5 {
+                let mut bag: Vec<u8> = (0..12 * copies)
+                    .map(|_| randomizer.next_shape(&mut rng, &state))
+                    .collect();
+                bag.sort();
+
+                let expected: Vec<u8> = (0..12).flat_map(|id| vec![id; copies]).collect();
+                assert_eq!(bag, expected);
+            }
+        }
+
+        for name in ["uniform", "history"] {
+            let mut randomizer = randomizer::load(name, &pieces).unwrap();
+            assert!((0..100).all(|_| randomizer.next_shape(&mut rng, &state) < 12));
+        }
+
+        // history rerolls make immediate repeats rare
+        let mut history = randomizer::load("history", &pieces).unwrap();
+        let shapes: Vec<u8> = (0..1000)
+            .map(|_| history.next_shape(&mut rng, &state))
+            .collect();
+        let repeats = shapes.windows(2).filter(|w| w[0] == w[1]).count();
+        assert!(repeats < 10, "{} repeats", repeats);
+
+        // a single piece is always among the last ones dealt after the first draw, so
+        // the next one takes the draw and all 4 rerolls
+        let draws = |count: usize| {
+            let mut rng: rand::rngs::StdRng = rand::SeedableRng::seed_from_u64(1);
+            for _ in 0..count {
+                rng.gen_range(0..1usize);
+            }
+            rng.gen::<u64>()
+        };
+
+        let mut history = randomizer::History::new(1);
+        let mut history_rng: rand::rngs::StdRng = rand::SeedableRng::seed_from_u64(1);
+        history.next_shape(&mut history_rng, &state);
+        history.next_shape(&mut history_rng, &state);
+
+        let next = history_rng.gen::<u64>();
+        assert_eq!(next, draws(1 + 1 + 4));
+        assert_ne!(next, draws(1 + 1 + 3));
+
+        let mut sequence =
+            randomizer::Sequence::parse("test", "# comment\nX I\nLL\n", &pieces).unwrap();
+        let shapes: Vec<u8> = (0..6)
+            .map(|_| sequence.next_shape(&mut rng, &state))
+            .collect();
+        assert_eq!(shapes, vec![0, 1, 8, 8, 0, 1]);
+
+        assert!(randomizer::Sequence::parse("test", "XQ", &pieces).is_err());
+        assert!(randomizer::Sequence::parse("test", "# only a comment", &pieces).is_err());
+    }
+
+    #[test]
+    fn test_blocked_out() {
+        let pieces = PieceSet::pentominoes();
+
+        let mut state = State::new(crate::DEFAULT_LOOKAHEAD_SIZE, GameConfig::default());
+        state.remaining_pieces = vec![1];
+
+        assert!(!is_blocked_out(&state, &pieces));
+
+        // a single tile in the spawn area of the vertical I
+        state.field.set(2, 2, create_composite_id(0, 0));
+        assert!(is_blocked_out(&state, &pieces));
+
+        state.remaining_pieces.clear();
+        assert!(!is_blocked_out(&state, &pieces));
+    }
+
+    #[test]
+    fn test_spawn_narrow_board() {
+        // the hexomino I lies flat in permutation 0, wider than the default board
+        let pieces = PieceSet::hexominoes();
+
+        let mut state = State::new(crate::DEFAULT_LOOKAHEAD_SIZE, GameConfig::default());
+
+        for piece_id in 0..pieces.len() as u8 {
+            state.remaining_pieces = vec![piece_id];
+            assert!(!is_blocked_out(&state, &pieces), "piece {}", piece_id);
+
+            let piece_moves = pieces.moves(piece_id);
+            let (permutation, _, _) = piece_moves.spawn_position(&state.field).unwrap();
+            assert!(piece_moves.shapes[permutation].width <= state.field.width());
+        }
+    }
+
+    #[test]
+    fn test_scoring() {
+        let rules = scoring::ScoringRules::default();
+
+        assert_eq!(rules.points(&[1], 1, false, 1), 100);
+        // cascade: second pass scores its own lines plus the cascade bonus
+        assert_eq!(rules.points(&[2, 1], 1, false, 1), 300 + 100 + 50);
+        // 7 rows at once score as the largest listed clear
+        assert_eq!(rules.points(&[7], 1, false, 2), 1600 * 2);
+        assert_eq!(rules.points(&[1], 3, true, 1), 100 + 2 * 50 + 3000);
+
+        let mut state = State::new(crate::DEFAULT_LOOKAHEAD_SIZE, GameConfig::default());
+        state.field.set(14, 0, create_composite_id(0, 0));
+
+        assert_eq!(scoring::record_clears(&mut state, &[1]), 100);
+        assert_eq!(scoring::record_clears(&mut state, &[1]), 150);
+        assert_eq!(state.combo, 2);
+
+        assert_eq!(scoring::record_clears(&mut state, &[]), 0);
+        assert_eq!(state.combo, 0);
+        assert_eq!((state.score, state.cleared_rows), (250, 2));
+
+        // the field is empty once its only tile is gone
+        state.field.set(14, 0, EMPTY);
+        assert_eq!(scoring::record_clears(&mut state, &[1]), 3100);
+
+        let rules: scoring::ScoringRules = "lines=10/20, combo=0,level=5".parse().unwrap();
+        assert_eq!(rules.line_points, [10, 20, 20, 20, 20, 20]);
+        assert_eq!(rules.combo_points, 0);
+        assert_eq!(rules.level(12), 3);
+
+        assert!("lines=1/2/3/4/5/6/7"
+            .parse::<scoring::ScoringRules>()
+            .is_err());
+        assert!("level=0".parse::<scoring::ScoringRules>().is_err());
+        assert!("speed=1".parse::<scoring::ScoringRules>().is_err());
+        assert!("combo".parse::<scoring::ScoringRules>().is_err());
+    }
+
+    #[test]
+    fn test_gravity_rules() {
+        let a = create_composite_id(0, 0);
+        let b = create_composite_id(1, 1);
+        let c = create_composite_id(2, 2);
+        let d = create_composite_id(3, 3);
+
+        // A hangs over an empty column once the B row is cleared
+        let field: GameField = vec![
+            vec![a, a, EMPTY, EMPTY],
+            vec![b, b, b, b],
+            vec![EMPTY, EMPTY, EMPTY, c],
+            vec![d, EMPTY, EMPTY, c],
+        ]
+        .into();
+
+        for (rule, expected) in [
+            // down by exactly the one cleared row
+            (GravityRule::Naive, [0, 0b0011, 0b1000, 0b1001]),
+            // as a whole, until it rests on D
+            (GravityRule::Sticky, [0, 0, 0b1011, 0b1001]),
+            // tile by tile, one on D, the other to the floor
+            (GravityRule::FreeFall, [0, 0, 0b1001, 0b1011]),
+        ] {
+            let mut config = GameConfig::new(4, 4);
+            config.gravity = rule;
+
+            let mut state = State::new(1, config);
+            state.field = field.clone();
+
+            assert_eq!(update(&mut state, &mut IdManager::default()), 1, "{}", rule);
+            assert_eq!(state.field.occupancy(), expected, "{}", rule);
+
+            let rows: Vec<Vec<u32>> = state.field.rows().map(<[u32]>::to_vec).collect();
+            assert_eq!(state.field.zobrist(), GameField::from(rows).zobrist());
+
+            assert_eq!(rule.to_string().parse(), Ok(rule));
+        }
+
+        assert!("gravity".parse::<GravityRule>().is_err());
+    }
+
+    #[test]
+    fn test_garbage() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let mut id_manager = IdManager::default();
+
+        let mut config = GameConfig::new(5, 4);
+        config.garbage_interval = 2;
+
+        let mut state = State::new(1, config);
+        let x = create_composite_id(0, id_manager.next_unique_id());
+        state.field.set(3, 0, x);
+        state.pieces_placed = 1;
+
+        // not due yet
+        assert!(!garbage::push_if_due(&mut state, &mut rng, &mut id_manager));
+
+        state.pieces_placed = 2;
+        assert!(garbage::push_if_due(&mut state, &mut rng, &mut id_manager));
+
+        // everything moved up a row, the new one has exactly one gap
+        assert_eq!(state.field.get(2, 0), x);
+        assert_eq!(state.field.occupancy()[3].count_ones(), 4);
+        assert!(!state.field.is_row_full(3));
+        let gap = (0..5).find(|&col| state.field.is_empty(3, col)).unwrap();
+        let garbage_tile = state.field.get(3, (gap + 1) % 5);
+        assert_eq!(get_piece_id(garbage_tile), garbage::GARBAGE_PIECE_ID);
+
+        let rows: Vec<Vec<u32>> = state.field.rows().map(<[u32]>::to_vec).collect();
+        assert_eq!(state.field.zobrist(), GameField::from(rows).zobrist());
+
+        // filling the gap clears the garbage row like any other, the piece above falls back
+        state
+            .field
+            .set(3, gap, create_composite_id(1, id_manager.next_unique_id()));
+        assert_eq!(update(&mut state, &mut id_manager), 1);
+        assert!(!state.field.is_empty(3, 0));
+        assert_eq!(
+            state
+                .field
+                .occupancy()
+                .iter()
+                .map(|mask| mask.count_ones())
+                .sum::<u32>(),
+            1
+        );
+
+        // pushed off the top
+        for row in 0..4 {
+            state.field.set(row, 4, x);
+        }
+        state.pieces_placed = 4;
+        assert!(garbage::push_if_due(&mut state, &mut rng, &mut id_manager));
+        assert_eq!(state.status, GameStatus::GameOver);
+    }
+
+    #[test]
+    fn test_zobrist() {
+        let recomputed = |field: &GameField| {
+            field
+                .occupancy()
+                .iter()
+                .enumerate()
+                .fold(0, |hash, (row, &mask)| hash ^ zobrist::row_key(row, mask))
+        };
+
+        let pieces = PieceSet::pentominoes();
+        let i_vertical = &pieces.moves(1).shapes[0];
+        let x = &pieces.moves(0).shapes[0];
+
+        // the same pieces placed in either order, with different ids
+        let mut a = GameField::new(5, 15);
+        a.place(i_vertical, create_composite_id(1, 0), 10, 0);
+        a.place(x, create_composite_id(0, 1), 12, 2);
+
+        let mut b = GameField::new(5, 15);
+        b.place(x, create_composite_id(0, 7), 12, 2);
+        b.place(i_vertical, create_composite_id(1, 8), 10, 0);
+
+        assert_ne!(a, b);
+        assert_eq!(a.zobrist(), b.zobrist());
+        assert_eq!(a.zobrist(), recomputed(&a));
+
+        // still up to date after clears and gravity
+        let mut state = State::new(2, GameConfig::default());
+        let filler = create_composite_id(9, 2);
+        for row in 12..15 {
+            for col in 0..4 {
+                state.field.set(row, col, filler);
+            }
+        }
+        state
+            .field
+            .place(i_vertical, create_composite_id(1, 3), 10, 4);
+        state.field.place(x, create_composite_id(0, 4), 7, 2);
+
+        update(&mut state, &mut IdManager::default());
+        assert_eq!(state.field.zobrist(), recomputed(&state.field));
+
+        // the preview is part of the position
+        state.remaining_pieces = vec![3, 5];
+        let before = state.zobrist();
+        state.remaining_pieces = vec![5];
+        assert_ne!(state.zobrist(), before);
+
+        // removing the first piece only takes out its own key, whatever follows it
+        assert_eq!(
+            zobrist::pieces_key(&[3, 5]) ^ zobrist::pieces_key(&[5]),
+            zobrist::pieces_key(&[3, 9]) ^ zobrist::pieces_key(&[9])
+        );
+
+        // and so is the held piece
+        let before = state.zobrist();
+        state.held_piece = Some(3);
+        assert_ne!(state.zobrist(), before);
+        state.held_piece = Some(5);
+        assert_ne!(state.zobrist(), before);
+    }
+
+    #[test]
+    fn test_zobrist_sticky_grouping() {
+        // the same tiles above a full row, as a bar on two single tiles, or as two
+        // vertical pairs and a single tile, ids clear of those handed out by `update()`
+        let grouped = |groups: &[&[(usize, usize)]], first_id: u32| {
+            let mut state = State::new(2, GameConfig::default());
+            for col in 0..5 {
+                state.field.set(14, col, create_composite_id(9, 0));
+            }
+            for (i, group) in groups.iter().enumerate() {
+                let composite_id = create_composite_id(2, first_id + i as u32);
+                for &(row, col) in group.iter() {
+                    state.field.set(row, col, composite_id);
+                }
+            }
+            state
+        };
+
+        let bar = grouped(
+            &[&[(12, 0), (12, 1), (12, 2)], &[(13, 0)], &[(13, 2)]],
+            1000,
+        );
+        let pairs = grouped(
+            &[&[(12, 0), (13, 0)], &[(12, 2), (13, 2)], &[(12, 1)]],
+            1000,
+        );
+
+        assert_eq!(bar.field.occupancy(), pairs.field.occupancy());
+        assert_eq!(bar.field.zobrist(), pairs.field.zobrist());
+        assert_ne!(bar.zobrist(), pairs.zobrist());
+
+        // the ids themselves don't matter
+        let renamed = grouped(
+            &[&[(12, 0), (13, 0)], &[(12, 2), (13, 2)], &[(12, 1)]],
+            2000,
+        );
+        assert_eq!(pairs.zobrist(), renamed.zobrist());
+
+        // they do not end up the same: the bar rests on both tiles, the middle tile drops
+        let mut id_manager = IdManager::default();
+        let (mut bar_after, mut pairs_after) = (bar.clone(), pairs.clone());
+        update(&mut bar_after, &mut id_manager);
+        update(&mut pairs_after, &mut id_manager);
+        assert_ne!(bar_after.field.occupancy(), pairs_after.field.occupancy());
+
+        // where tiles fall on their own, or rows shift as a whole, only the tiles count
+        for gravity in [GravityRule::Naive, GravityRule::FreeFall] {
+            let (mut bar, mut pairs) = (bar.clone(), pairs.clone());
+            bar.config.gravity = gravity;
+            pairs.config.gravity = gravity;
+            assert_eq!(bar.zobrist(), pairs.zobrist());
+        }
+    }
 }
@@ -0,0 +1,268 @@
+use crate::game::{GameField, ShapeMask};
+use crate::pieces::{self, Shape};
+
+use std::collections::VecDeque;
+
+// (permutation, row, col), where [row][col] is the top-left of the permutation
+pub type Position = (usize, usize, usize);
+
+// horizontal offsets tried, in order, when a rotation or flip collides
+const KICKS: [i32; 3] = [0, -1, 1];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Move {
+    Left,
+    Right,
+    RotateCw,
+    RotateCcw,
+    // only does anything for pieces whose permutations include their mirror image
+    Flip,
+    SoftDrop,
+    // drops to the lowest free row and locks the piece
+    HardDrop,
+}
+
+// a reachable resting position and the inputs leading to it from spawn
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Landing {
+    pub permutation: usize,
+    // top-left of the permutation's bounding box
+    pub row: usize,
+    pub col: usize,
+    pub path: Vec<Move>,
+}
+
+// all permutations of a piece as bitmasks,
+// along with the permutation each rotation/flip leads to
+#[derive(Clone, Debug)]
+pub struct PieceMoves {
+    pub shapes: Vec<ShapeMask>,
+    rotate_cw: Vec<Option<usize>>,
+    rotate_ccw: Vec<Option<usize>>,
+    flip: Vec<Option<usize>>,
+}
+
+impl PieceMoves {
+    pub fn new(permutations: &[Shape]) -> Self {
+        let find = |target: &Shape| permutations.iter().position(|shape| shape == target);
+
+        PieceMoves {
+            shapes: permutations
+                .iter()
+                .map(|shape| ShapeMask::new(shape))
+                .collect(),
+            rotate_cw: permutations
+                .iter()
+                .map(|s| find(&pieces::rotate_cw(s)))
+                .collect(),
+            rotate_ccw: permutations
+                .iter()
+                .map(|s| find(&pieces::rotate_ccw(s)))
+                .collect(),
+            flip: permutations
+                .iter()
+                .map(|s| find(&pieces::flip(s)))
+                .collect(),
+        }
+    }
+
+    // spawn is the first permutation as wide as the board at most, usually permutation 0,
+    // horizontally centered on the top row
+    // None if it overlaps a tile there, or no permutation is narrow enough
+    pub fn spawn_position(&self, field: &GameField) -> Option<Position> {
+        let (permutation, shape) = self
+            .shapes
+            .iter()
+            .enumerate()
+            .find(|(_, shape)| shape.width <= field.width())?;
+
+        let col = (field.width() - shape.width) / 2;
+
+        if !field.fits(shape, 0, col) {
+            return None;
+        }
+
+        Some((permutation, 0, col))
+    }
+
+    // applies a single move, returning the resulting position if it is legal
+    fn apply(
+        &self,
+        field: &GameField,
+        (permutation, row, col): Position,
+        input: Move,
+    ) -> Option<Position> {
+        let shape = &self.shapes[permutation];
+
+        match input {
+            Move::Left => {
+                let col = col.checked_sub(1)?;
+                field
+                    .fits(shape, row, col)
+                    .then_some((permutation, row, col))
+            }
+            Move::Right => field
+                .fits(shape, row, col + 1)
+                .then_some((permutation, row, col + 1)),
+            Move::SoftDrop => {
+                field
+                    .fits(shape, row + 1, col)
+                    .then_some((permutation, row + 1, col))
+            }
+            Move::RotateCw => self.turn(field, self.rotate_cw[permutation]?, row, col, shape),
+            Move::RotateCcw => self.turn(field, self.rotate_ccw[permutation]?, row, col, shape),
+            Move::Flip => self.turn(field, self.flip[permutation]?, row, col, shape),
+            Move::HardDrop => {
+                let mut row = row;
+                while field.fits(shape, row + 1, col) {
+                    row += 1;
+                }
+                Some((permutation, row, col))
+            }
+        }
+    }
+
+    // swaps to `target` permutation, keeping the bounding box centered
+    fn turn(
+        &self,
+        field: &GameField,
+        target: usize,
+        row: usize,
+        col: usize,
+        from: &ShapeMask,
+    ) -> Option<Position> {
+        let to = &self.shapes[target];
+
+        let row = row as i32 + (from.height as i32 - to.height as i32) / 2;
+        let col = col as i32 + (from.width as i32 - to.width as i32) / 2;
+
+        if row < 0 {
+            return None;
+        }
+
+        KICKS.iter().find_map(|kick| {
+            let col = col + kick;
+
+            if col < 0 || !field.fits(to, row as usize, col as usize) {
+                return None;
+            }
+
+            Some((target, row as usize, col as usize))
+        })
+    }
+
+    // every resting position a falling piece can actually reach from spawn,
+    // along with the shortest input path to each of them
+    pub fn reachable_landings(&self, field: &GameField) -> Vec<Landing> {
+        let (resting, came_from) = self.explore(field);
+
+        resting
+            .into_iter()
+            .map(|position| Landing {
+                permutation: position.0,
+                row: position.1,
+                col: position.2,
+                path: self.path_to(field, position, &came_from),
+            })
+            .collect()
+    }
+
+    fn index(&self, field: &GameField, (permutation, row, col): Position) -> usize {
+        (permutation * field.height() + row) * field.width() + col
+    }
+
+    // breadth-first search over (permutation, row, col) from spawn,
+    // returns resting positions and the (previous position, move) leading to each visited one
+    fn explore(&self, field: &GameField) -> (Vec<Position>, Vec<Option<(Position, Move)>>) {
+        let mut resting = Vec::new();
+
+        let size = self.shapes.len() * field.width() * field.height();
+        let mut visited = vec![false; size];
+        let mut came_from = vec![None; size];
+
+        let spawn = match self.spawn_position(field) {
+            Some(spawn) => spawn,
+            None => return (resting, came_from),
+        };
+
+        let mut queue = VecDeque::new();
+
+        visited[self.index(field, spawn)] = true;
+        queue.push_back(spawn);
+
+        while let Some(position) = queue.pop_front() {
+            // cannot move down any further, hence the piece locks here
+            if self.apply(field, position, Move::SoftDrop).is_none() {
+                resting.push(position);
+            }
+
+            for input in [
+                Move::Left,
+                Move::Right,
+                Move::RotateCw,
+                Move::RotateCcw,
+                Move::Flip,
+                Move::SoftDrop,
+            ] {
+                let Some(next) = self.apply(field, position, input) else {
+                    continue;
+                };
+
+                let next_index = self.index(field, next);
+
+                if visited[next_index] {
+                    continue;
+                }
+
+                visited[next_index] = true;
+                came_from[next_index] = Some((position, input));
+                queue.push_back(next);
+            }
+        }
+
+        (resting, came_from)
+    }
+
+    // replays `path` from spawn, returning where the piece locks,
+    // or None if any input is illegal or the path does not end with a hard drop
+    pub fn follow_path(&self, field: &GameField, path: &[Move]) -> Option<Position> {
+        let (last, inputs) = path.split_last()?;
+
+        if *last != Move::HardDrop {
+            return None;
+        }
+
+        let position = inputs
+            .iter()
+            .try_fold(self.spawn_position(field)?, |position, &input| {
+                self.apply(field, position, input)
+            })?;
+
+        self.apply(field, position, Move::HardDrop)
+    }
+
+    // walks `came_from` back to spawn, then collapses the trailing
+    // soft drops into the hard drop that locks the piece
+    fn path_to(
+        &self,
+        field: &GameField,
+        mut position: Position,
+        came_from: &[Option<(Position, Move)>],
+    ) -> Vec<Move> {
+        let mut path = Vec::new();
+
+        while let Some((previous, input)) = came_from[self.index(field, position)] {
+            path.push(input);
+            position = previous;
+        }
+
+        path.reverse();
+
+        while path.last() == Some(&Move::SoftDrop) {
+            path.pop();
+        }
+
+        path.push(Move::HardDrop);
+        path
+    }
+}
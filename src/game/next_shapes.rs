@@ -1,64 +1,67 @@
-use crate::game;
-
 use std::collections::VecDeque;
 
-use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::game::{randomizer::Randomizer, State};
 
+// shapes are piece ids, see `pieces::PieceSet`
+// the same seed and randomizer always yield the same sequence of shapes
 pub struct NextShapes {
-    all_shapes: Vec<char>,
-    available_shapes: Vec<char>,
-    next_up_shapes: VecDeque<char>,
+    seed: u64,
+    rng: StdRng,
+    randomizer: Box<dyn Randomizer>,
+    next_up_shapes: VecDeque<u8>,
     stack_size: usize,
 }
 
-// TODO refactor this bs
 impl NextShapes {
-    pub fn new(lookahead_size: u8) -> NextShapes {
-        let mut next_shapes = NextShapes {
-            all_shapes: vec!['X', 'V', 'Z', 'W', 'I', 'T', 'Y', 'L', 'N', 'P', 'U', 'F'],
-            available_shapes: Vec::new(),
+    pub fn new(lookahead_size: u8, randomizer: Box<dyn Randomizer>, seed: u64) -> NextShapes {
+        // shapes are only drawn once a state is known, see `get_next_stack()`
+        NextShapes {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            randomizer,
             next_up_shapes: VecDeque::new(),
             stack_size: lookahead_size as usize,
-        };
-
-        next_shapes.generate_next_up_shapes();
-        next_shapes
+        }
     }
 
-    fn refresh(&mut self) {
-        self.available_shapes.clear();
-        self.available_shapes.extend(&self.all_shapes);
-        let mut rng = thread_rng();
-        self.available_shapes.shuffle(&mut rng);
+    pub fn seed(&self) -> u64 {
+        self.seed
     }
 
-    fn generate_next_up_shapes(&mut self) {
-        self.next_up_shapes.clear();
-        for _ in 0..self.stack_size {
-            let next = self.get_next_shape_from_pool();
-            self.next_up_shapes.push_back(next);
-        }
+    pub fn randomizer_name(&self) -> String {
+        self.randomizer.name()
     }
 
-    fn get_next_shape_from_pool(&mut self) -> char {
-        if self.available_shapes.is_empty() {
-            self.refresh();
-        }
-        self.available_shapes.remove(0)
+    // the shapes that can come right after the last stack returned by `get_next_stack()`,
+    // see `Randomizer::possible_shapes()`
+    pub fn possible_next_shapes(&self) -> Vec<u8> {
+        self.randomizer.possible_shapes()
     }
 
-    pub fn get_next_stack(&mut self) -> Vec<char> {
-        let mut next_stack = Vec::with_capacity(self.stack_size);
+    // the next `lookahead_size` shapes, given the state they are about to be placed on
+    pub fn get_next_stack(&mut self, state: &State) -> Vec<u8> {
+        while self.next_up_shapes.len() < self.stack_size {
+            // see `Randomizer::next_shape()`
+            let mut upcoming = state.clone();
+            upcoming.remaining_pieces = self.next_up_shapes.iter().copied().collect();
 
-        for i in 0..self.stack_size {
-            next_stack.push(self.next_up_shapes[i]);
+            let next = self.randomizer.next_shape(&mut self.rng, &upcoming);
+            self.next_up_shapes.push_back(next);
         }
 
+        let next_stack = self.next_up_shapes.iter().copied().collect();
+
         self.next_up_shapes.pop_front();
-        let next = self.get_next_shape_from_pool();
-        self.next_up_shapes.push_back(next);
 
         next_stack
     }
+
+    // takes the second shape of the last stack off the queue as well, for when it went
+    // straight into play because the current one was put on hold
+    pub fn skip(&mut self) {
+        self.next_up_shapes.pop_front();
+    }
 }
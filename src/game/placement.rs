@@ -0,0 +1,100 @@
+use crate::game::{self, movement::Move, IdManager, State};
+use crate::pieces::PieceSet;
+
+// where a piece goes, enough to log a move, replay it, or check it against the rules
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Placement {
+    // see `pieces::PieceSet`
+    pub piece_id: u8,
+    // index into the piece's permutations, see `PieceMoves::shapes`
+    pub permutation: usize,
+    // top-left of the permutation's bounding box
+    pub row: usize,
+    pub col: usize,
+    // the inputs leading there from spawn, ending with a hard drop
+    pub path: Vec<Move>,
+    // the current piece is put on hold first, `piece_id` is then the one that was held,
+    // or the one after the current piece if none was
+    pub hold: bool,
+}
+
+impl std::fmt::Display for Placement {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "piece {}, permutation {}, row {}, col {}, path {:?}",
+            self.piece_id, self.permutation, self.row, self.col, self.path
+        )?;
+
+        if self.hold {
+            write!(f, ", after hold")?;
+        }
+
+        Ok(())
+    }
+}
+
+// places the first of `state.remaining_pieces` as `placement` says, or the piece swapped
+// in for it by holding, rows are not cleared, see `game::update()`
+// fails without touching `state` if the placement does not follow the rules: wrong piece,
+// hold while it is disabled, or a path that is illegal from spawn or does not lock the
+// piece where it says
+pub fn apply_placement(
+    state: &mut State,
+    placement: &Placement,
+    pieces: &PieceSet,
+    id_manager: &mut IdManager,
+) -> Result<(), String> {
+    let Some(&current_piece) = state.remaining_pieces.first() else {
+        return Err(String::from("there is no piece to place"));
+    };
+
+    // the piece to place, and how many pieces it takes off the preview
+    let (piece_id, consumed) = match (placement.hold, state.held_piece) {
+        (false, _) => (current_piece, 1),
+        (true, _) if !state.config.hold => return Err(String::from("hold is disabled")),
+        (true, Some(held_piece)) => (held_piece, 1),
+        (true, None) => match state.remaining_pieces.get(1) {
+            Some(&next_piece) => (next_piece, 2),
+            None => return Err(String::from("nothing is held and there is no next piece")),
+        },
+    };
+
+    if piece_id != placement.piece_id {
+        return Err(format!(
+            "placement is for piece {}, the piece to place is {}",
+            placement.piece_id, piece_id
+        ));
+    }
+
+    let piece_moves = pieces.moves(placement.piece_id);
+    let target = (placement.permutation, placement.row, placement.col);
+
+    match piece_moves.follow_path(&state.field, &placement.path) {
+        Some(position) if position == target => {}
+        Some(position) => {
+            return Err(format!(
+                "path locks the piece at {:?}, not at {:?}",
+                position, target
+            ))
+        }
+        None => return Err(format!("path {:?} is not legal from spawn", placement.path)),
+    }
+
+    let composite_id = game::create_composite_id(placement.piece_id, id_manager.next_unique_id());
+
+    state.field.place(
+        &piece_moves.shapes[placement.permutation],
+        composite_id,
+        placement.row,
+        placement.col,
+    );
+    state.remaining_pieces.drain(..consumed);
+    state.pieces_placed += 1;
+
+    if placement.hold {
+        state.held_piece = Some(current_piece);
+    }
+
+    Ok(())
+}
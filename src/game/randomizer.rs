@@ -0,0 +1,210 @@
+use crate::game::State;
+use crate::pieces::PieceSet;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+// pieces remembered by the history randomizer, and rerolls before giving up
+const HISTORY_SIZE: usize = 4;
+const HISTORY_REROLLS: usize = 4;
+
+// decides which piece comes next, shapes are piece ids, see `pieces::PieceSet`
+// all randomness comes from the rng handed in by `NextShapes`, so the sequence
+// is fully determined by its seed
+// `state` is the game as it stands when the shape is drawn, its `remaining_pieces` are the
+// shapes drawn before this one that are yet to be placed
+// only the adversarial randomizer (`bot::Adversary`) looks at it
+pub trait Randomizer {
+    fn name(&self) -> String;
+
+    fn next_shape(&mut self, rng: &mut StdRng, state: &State) -> u8;
+
+    // the shapes the next call to `next_shape()` could return,
+    // listed as many times as they are likely relative to each other
+    fn possible_shapes(&self) -> Vec<u8>;
+}
+
+// a preset name, or the path to a sequence file otherwise
+pub fn load(name: &str, pieces: &PieceSet) -> Result<Box<dyn Randomizer>, String> {
+    let piece_count = pieces.len();
+
+    match name {
+        "bag" => Ok(Box::new(Bag::new(piece_count, 1))),
+        "double-bag" => Ok(Box::new(Bag::new(piece_count, 2))),
+        "uniform" => Ok(Box::new(Uniform { piece_count })),
+        "history" => Ok(Box::new(History::new(piece_count))),
+        path => Ok(Box::new(Sequence::from_file(path, pieces)?)),
+    }
+}
+
+// every piece `copies` times per shuffled bag
+pub struct Bag {
+    copies: usize,
+    all_shapes: Vec<u8>,
+    available_shapes: Vec<u8>,
+}
+
+impl Bag {
+    pub fn new(piece_count: usize, copies: usize) -> Self {
+        Bag {
+            copies,
+            all_shapes: (0..piece_count as u8)
+                .flat_map(|shape| std::iter::repeat_n(shape, copies))
+                .collect(),
+            available_shapes: Vec::new(),
+        }
+    }
+}
+
+impl Randomizer for Bag {
+    fn name(&self) -> String {
+        match self.copies {
+            1 => String::from("bag"),
+            2 => String::from("double-bag"),
+            copies => format!("{}x bag", copies),
+        }
+    }
+
+    fn next_shape(&mut self, rng: &mut StdRng, _state: &State) -> u8 {
+        if self.available_shapes.is_empty() {
+            self.available_shapes.extend(&self.all_shapes);
+            self.available_shapes.shuffle(rng);
+        }
+
+        self.available_shapes.remove(0)
+    }
+
+    fn possible_shapes(&self) -> Vec<u8> {
+        if self.available_shapes.is_empty() {
+            return self.all_shapes.clone();
+        }
+
+        self.available_shapes.clone()
+    }
+}
+
+// independent draws, droughts and floods included
+pub struct Uniform {
+    piece_count: usize,
+}
+
+impl Randomizer for Uniform {
+    fn name(&self) -> String {
+        String::from("uniform")
+    }
+
+    fn next_shape(&mut self, rng: &mut StdRng, _state: &State) -> u8 {
+        rng.gen_range(0..self.piece_count) as u8
+    }
+
+    fn possible_shapes(&self) -> Vec<u8> {
+        (0..self.piece_count as u8).collect()
+    }
+}
+
+// TGM-style: rerolls a few times while the draw is among the last few pieces dealt
+pub struct History {
+    piece_count: usize,
+    history: Vec<u8>,
+}
+
+impl History {
+    pub fn new(piece_count: usize) -> Self {
+        History {
+            piece_count,
+            history: Vec::with_capacity(HISTORY_SIZE),
+        }
+    }
+}
+
+impl Randomizer for History {
+    fn name(&self) -> String {
+        String::from("history")
+    }
+
+    fn next_shape(&mut self, rng: &mut StdRng, _state: &State) -> u8 {
+        let mut shape = rng.gen_range(0..self.piece_count) as u8;
+
+        for _ in 0..HISTORY_REROLLS {
+            if !self.history.contains(&shape) {
+                break;
+            }
+            shape = rng.gen_range(0..self.piece_count) as u8;
+        }
+
+        if self.history.len() == HISTORY_SIZE {
+            self.history.remove(0);
+        }
+        self.history.push(shape);
+
+        shape
+    }
+
+    // rerolls make recent shapes less likely, not impossible
+    fn possible_shapes(&self) -> Vec<u8> {
+        (0..self.piece_count as u8).collect()
+    }
+}
+
+// a fixed sequence, starting over once exhausted
+pub struct Sequence {
+    name: String,
+    shapes: Vec<u8>,
+    next: usize,
+}
+
+impl Sequence {
+    pub fn from_file(path: &str, pieces: &PieceSet) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("could not read sequence file '{}': {}", path, err))?;
+
+        Self::parse(path, &text, pieces)
+    }
+
+    // piece symbols, whitespace is ignored and so are lines starting with '#'
+    pub fn parse(name: &str, text: &str, pieces: &PieceSet) -> Result<Self, String> {
+        let mut shapes = Vec::new();
+
+        for (line_number, line) in text.lines().enumerate().map(|(i, l)| (i + 1, l.trim())) {
+            if line.starts_with('#') {
+                continue;
+            }
+
+            for symbol in line.chars().filter(|c| !c.is_whitespace()) {
+                let shape = pieces.id_of(symbol).ok_or(format!(
+                    "{}:{}: unknown piece '{}' in {}",
+                    name, line_number, symbol, pieces.name
+                ))?;
+
+                shapes.push(shape);
+            }
+        }
+
+        if shapes.is_empty() {
+            return Err(format!("{}: empty piece sequence", name));
+        }
+
+        Ok(Sequence {
+            name: String::from(name),
+            shapes,
+            next: 0,
+        })
+    }
+}
+
+impl Randomizer for Sequence {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn next_shape(&mut self, _rng: &mut StdRng, _state: &State) -> u8 {
+        let shape = self.shapes[self.next];
+        self.next = (self.next + 1) % self.shapes.len();
+        shape
+    }
+
+    fn possible_shapes(&self) -> Vec<u8> {
+        vec![self.shapes[self.next]]
+    }
+}
@@ -0,0 +1,149 @@
+use crate::game::State;
+
+use std::str::FromStr;
+
+// points are listed for up to this many rows cleared in a single pass,
+// larger clears score as much as the last entry
+pub const MAX_SCORED_CLEAR: usize = 6;
+
+// all points are multiplied by the current level
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScoringRules {
+    // points for 1, 2, ... rows cleared in the same pass
+    pub line_points: [u32; MAX_SCORED_CLEAR],
+    // extra points for each pass after the first, times the pass index,
+    // see the recursion in `game::update()`
+    pub cascade_points: u32,
+    // extra points when a placement leaves the field empty
+    pub perfect_clear_points: u32,
+    // extra points for each consecutive clearing placement after the first
+    pub combo_points: u32,
+    pub lines_per_level: u32,
+}
+
+impl Default for ScoringRules {
+    fn default() -> Self {
+        ScoringRules {
+            line_points: [100, 300, 500, 800, 1200, 1600],
+            cascade_points: 50,
+            perfect_clear_points: 3000,
+            combo_points: 50,
+            lines_per_level: 10,
+        }
+    }
+}
+
+impl ScoringRules {
+    pub fn level(&self, cleared_rows: u32) -> u32 {
+        1 + cleared_rows / self.lines_per_level
+    }
+
+    // points for a placement that cleared `passes[i]` rows on pass i,
+    // `combo` counts this placement
+    pub fn points(&self, passes: &[u32], combo: u32, perfect_clear: bool, level: u32) -> u64 {
+        let mut points: u64 = 0;
+
+        for (pass, &cleared) in passes.iter().enumerate() {
+            if cleared == 0 {
+                continue;
+            }
+
+            let index = (cleared as usize).min(MAX_SCORED_CLEAR) - 1;
+            points += self.line_points[index] as u64;
+            points += self.cascade_points as u64 * pass as u64;
+        }
+
+        if combo > 1 {
+            points += self.combo_points as u64 * (combo - 1) as u64;
+        }
+
+        if perfect_clear {
+            points += self.perfect_clear_points as u64;
+        }
+
+        points * level as u64
+    }
+}
+
+// scores a placement, given the rows it cleared on each pass of `game::update()`
+// returns the points gained
+pub fn record_clears(state: &mut State, passes: &[u32]) -> u64 {
+    let cleared: u32 = passes.iter().sum();
+
+    if cleared == 0 {
+        state.combo = 0;
+        return 0;
+    }
+
+    state.combo += 1;
+
+    let rules = state.config.scoring;
+    let level = rules.level(state.cleared_rows);
+    let perfect_clear = state.field.occupancy().iter().all(|&mask| mask == 0);
+
+    let points = rules.points(passes, state.combo, perfect_clear, level);
+
+    state.cleared_rows += cleared;
+    state.score += points;
+
+    points
+}
+
+// comma separated `<rule>=<value>` pairs, rules left out keep their default
+// e.g. "lines=100/300/500/800,cascade=0,perfect=1000,combo=50,level=10"
+impl FromStr for ScoringRules {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = ScoringRules::default();
+
+        for pair in s.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let (rule, value) = pair.split_once('=').ok_or(format!(
+                "invalid scoring rule '{}', expected <rule>=<value>",
+                pair
+            ))?;
+
+            let parse = |value: &str| {
+                value
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| format!("invalid value '{}' for scoring rule '{}'", value, rule))
+            };
+
+            match rule.trim() {
+                "lines" => {
+                    let points = value
+                        .split('/')
+                        .map(parse)
+                        .collect::<Result<Vec<u32>, String>>()?;
+
+                    if points.len() > MAX_SCORED_CLEAR {
+                        return Err(format!(
+                            "at most {} line point values, got {}",
+                            MAX_SCORED_CLEAR,
+                            points.len()
+                        ));
+                    }
+
+                    // larger clears than listed score as much as the last value
+                    for (index, line_points) in rules.line_points.iter_mut().enumerate() {
+                        *line_points = points[index.min(points.len() - 1)];
+                    }
+                }
+                "cascade" => rules.cascade_points = parse(value)?,
+                "perfect" => rules.perfect_clear_points = parse(value)?,
+                "combo" => rules.combo_points = parse(value)?,
+                "level" => {
+                    rules.lines_per_level = parse(value)?;
+
+                    if rules.lines_per_level == 0 {
+                        return Err(String::from("lines per level must be non-zero"));
+                    }
+                }
+                rule => return Err(format!("unknown scoring rule '{}'", rule)),
+            }
+        }
+
+        Ok(rules)
+    }
+}
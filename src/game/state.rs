@@ -1,60 +1,123 @@
-use crate::game;
+use crate::game::{self, GameField};
+use crate::pieces::PieceSet;
 use std::hash::{Hash, Hasher};
-use std::rc::Rc;
+use std::sync::Arc;
 
-// u16 has enough bits to store pentomino id and rotation
-pub type GameField = Vec<Vec<u16>>;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameStatus {
+    Playing,
+    // the next piece had nowhere to spawn
+    GameOver,
+}
 
 #[derive(Eq, Clone, Debug)]
 pub struct State {
-    // Rc<T> for multiple ownership of parent_state between children states
+    // Arc<T> for multiple ownership of parent_state between children states,
+    // which can be searched on different threads
     // also avoids inifinite size
-    pub parent_state: Option<Rc<State>>,
-    // Box<T> for self-referential data, avoids inifinite size
-    pub uncleared_state: Option<Box<State>>,
+    pub parent_state: Option<Arc<State>>,
+    // the placement that led here from the searched state, only kept on first
+    // generation states, see `bot::search()`
+    pub placement: Option<game::Placement>,
     pub field: GameField,
-    pub remaining_pieces: Vec<char>,
+    // piece ids, see `pieces::PieceSet`
+    pub remaining_pieces: Vec<u8>,
+    // pieces that can come after `remaining_pieces`, as many times as they are likely,
+    // see `Randomizer::possible_shapes()`, empty when unknown
+    pub possible_next_pieces: Vec<u8>,
+    // swapped out for a later piece, only ever set if `config.hold`
+    pub held_piece: Option<u8>,
     pub cleared_rows: u32,
+    // see `game::scoring`
+    pub score: u64,
+    // consecutive placements that cleared at least one row
+    pub combo: u32,
+    pub pieces_placed: u32,
+    pub status: GameStatus,
+    pub config: game::GameConfig,
 }
 
 impl State {
-    pub fn new(lookahead_size: u8) -> Self {
+    pub fn new(lookahead_size: u8, config: game::GameConfig) -> Self {
         State {
             parent_state: None,
-            uncleared_state: None,
-            field: vec![vec![game::EMPTY; game::FIELD_WIDTH]; game::FIELD_HEIGHT],
+            placement: None,
+            field: GameField::new(config.width, config.height),
             remaining_pieces: Vec::with_capacity(lookahead_size as usize),
+            possible_next_pieces: Vec::new(),
+            held_piece: None,
             cleared_rows: 0,
+            score: 0,
+            combo: 0,
+            pieces_placed: 0,
+            status: GameStatus::Playing,
+            config,
+        }
+    }
+}
+
+impl State {
+    // Zobrist hash of the position, the field, the preview and the held piece,
+    // and how tiles are grouped into pieces when those fall as a whole, see `game::zobrist`
+    // the field's part is kept up to date as tiles change, the rest is a few xors, but for
+    // the grouping which takes a pass over the field
+    pub fn zobrist(&self) -> u64 {
+        let grouping = match self.config.gravity {
+            game::GravityRule::Sticky => game::zobrist::grouping_key(&self.field),
+            game::GravityRule::Naive | game::GravityRule::FreeFall => 0,
+        };
+
+        self.field.zobrist()
+            ^ grouping
+            ^ game::zobrist::pieces_key(&self.remaining_pieces)
+            ^ game::zobrist::held_key(self.held_piece)
+    }
+
+    pub fn level(&self) -> u32 {
+        self.config.scoring.level(self.cleared_rows)
+    }
+
+    // the piece set is needed to print piece symbols
+    // debugging helper, mostly used by tests
+    #[allow(dead_code)]
+    pub fn display<'a>(&'a self, pieces: &'a PieceSet) -> StateDisplay<'a> {
+        StateDisplay {
+            state: self,
+            pieces,
         }
     }
 }
 
-impl std::fmt::Display for State {
+pub struct StateDisplay<'a> {
+    state: &'a State,
+    pieces: &'a PieceSet,
+}
+
+impl std::fmt::Display for StateDisplay<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        writeln!(f, "cleared rows: {}", self.cleared_rows)?;
+        writeln!(f, "cleared rows: {}", self.state.cleared_rows)?;
+        writeln!(f, "score: {}", self.state.score)?;
+        writeln!(f, "pieces placed: {}", self.state.pieces_placed)?;
+
+        if let Some(held) = self.state.held_piece {
+            writeln!(f, "held: {}", self.pieces.symbol(held))?;
+        }
+
+        if self.state.status == GameStatus::GameOver {
+            writeln!(f, "game over")?;
+        }
 
         writeln!(f, "cleared:")?;
-        for row in &self.field {
+        for row in self.state.field.rows() {
             for &tile in row {
                 if tile == game::EMPTY {
                     write!(f, "_ ")?;
                     continue;
                 }
 
-                let symbol = match game::get_pent_id(tile) {
-                    0 => 'X',
-                    1 => 'I',
-                    2 => 'Z',
-                    3 => 'T',
-                    4 => 'U',
-                    5 => 'V',
-                    6 => 'W',
-                    7 => 'Y',
-                    8 => 'L',
-                    9 => 'P',
-                    10 => 'N',
-                    11 => 'F',
-                    _ => '_',
+                let symbol = match game::get_piece_id(tile) {
+                    game::garbage::GARBAGE_PIECE_ID => '#',
+                    piece_id => self.pieces.symbol(piece_id),
                 };
                 write!(f, "{} ", symbol)?;
             }
@@ -67,20 +130,16 @@ impl std::fmt::Display for State {
 
 impl Hash for State {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        for piece in &self.remaining_pieces {
-            piece.hash(state);
-        }
-
-        for row in &self.field {
-            for &value in row {
-                value.hash(state);
-            }
-        }
+        // occupancy alone is enough to tell states apart in the search,
+        // equal states always have equal occupancy, and so equal Zobrist hashes
+        self.zobrist().hash(state);
     }
 }
 
 impl PartialEq for State {
     fn eq(&self, other: &Self) -> bool {
-        self.field == other.field && self.remaining_pieces == other.remaining_pieces
+        self.field == other.field
+            && self.remaining_pieces == other.remaining_pieces
+            && self.held_piece == other.held_piece
     }
 }
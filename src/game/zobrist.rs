@@ -0,0 +1,84 @@
+// Zobrist keys, one pseudo-random 64 bit key per feature of a position (an occupied cell,
+// a piece at some place in the preview), a position hashes to the xor of its keys,
+// so placing or removing a tile updates the hash with a single xor
+// keys are derived from the feature instead of looked up, so boards of any height work
+
+use crate::game::{self, GameField};
+
+// features past this are preview slots, every cell of a board fits below it
+const PIECE_FEATURES: u64 = 1 << 40;
+// past every preview slot
+const HELD_FEATURES: u64 = 1 << 41;
+// past every held piece, a tile of the same piece as the one to its right or below it
+const JOIN_FEATURES: u64 = 1 << 42;
+
+// splitmix64 finalizer
+fn key(feature: u64) -> u64 {
+    let mut z = feature.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+pub fn cell_key(row: usize, col: usize) -> u64 {
+    key(((row as u64) << 5) | col as u64)
+}
+
+// xor of the keys of every set bit of `mask`, a row of occupancy shifted by `col`
+pub fn row_key(row: usize, mut mask: u32) -> u64 {
+    let mut hash = 0;
+
+    while mask != 0 {
+        hash ^= cell_key(row, mask.trailing_zeros() as usize);
+        mask &= mask - 1;
+    }
+
+    hash
+}
+
+// pieces are keyed by their distance from the end of the preview, so removing the
+// first piece leaves the keys of the others as they were
+pub fn pieces_key(pieces: &[u8]) -> u64 {
+    pieces
+        .iter()
+        .rev()
+        .enumerate()
+        .fold(0, |hash, (slot, &piece)| {
+            hash ^ key(PIECE_FEATURES + ((slot as u64) << 8 | piece as u64))
+        })
+}
+
+// which neighbouring tiles are of the same piece, for `GravityRule::Sticky` where pieces
+// fall as a whole, so the same tiles grouped into other pieces end up elsewhere
+// tells the groups apart whatever ids they were given, unlike the tiles themselves, as
+// pieces are connected, but for garbage rows split by their gap, which a search never moves
+// recomputed from the whole field, not kept up to date like `GameField::zobrist()`
+pub fn grouping_key(field: &GameField) -> u64 {
+    let mut hash = 0;
+
+    for row in 0..field.height() {
+        for col in 0..field.width() {
+            let tile = field.get(row, col);
+
+            if tile == game::EMPTY {
+                continue;
+            }
+
+            if col + 1 < field.width() && field.get(row, col + 1) == tile {
+                hash ^= key(JOIN_FEATURES | ((row as u64) << 6 | (col as u64) << 1));
+            }
+            if row + 1 < field.height() && field.get(row + 1, col) == tile {
+                hash ^= key(JOIN_FEATURES | ((row as u64) << 6 | (col as u64) << 1 | 1));
+            }
+        }
+    }
+
+    hash
+}
+
+pub fn held_key(piece: Option<u8>) -> u64 {
+    match piece {
+        Some(piece) => key(HELD_FEATURES | piece as u64),
+        None => 0,
+    }
+}
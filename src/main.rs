@@ -2,16 +2,184 @@ mod app;
 mod bot;
 mod game;
 mod pentominoes;
+mod pieces;
+mod tuner;
 mod ui;
 
 const DEFAULT_N_RUNS: u32 = 100;
 const DEFAULT_N_SEARCHES: u32 = 100;
 const DEFAULT_LOOKAHEAD_SIZE: u8 = 5;
 
+const DEFAULT_TUNE_GENERATIONS: u32 = 20;
+const DEFAULT_TUNE_POPULATION_SIZE: usize = 16;
+const DEFAULT_TUNE_N_GAMES: u32 = 4;
+const DEFAULT_TUNE_N_SEARCHES: u32 = 200;
+const DEFAULT_TUNE_LOOKAHEAD_SIZE: u8 = 2;
+
+// keeps the frame loop responsive whatever the search, unless `--budget` is given
+const DEFAULT_GUI_BUDGET: bot::Budget = bot::Budget::Time(std::time::Duration::from_millis(200));
+
+// space taken by the side panel and window margins, in addition to the game field
+const PANEL_WIDTH: f32 = 245.0;
+const WINDOW_MARGIN: f32 = 15.0;
+
+// removes `--name <value>` from `args`, returning the value if present
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == name)?;
+
+    if index + 1 >= args.len() {
+        panic!("missing value for {}", name);
+    }
+
+    args.remove(index);
+    Some(args.remove(index))
+}
+
+// `adversarial` needs the bot's search, every other randomizer is in `game::randomizer`
+fn load_randomizer(
+    name: &str,
+    pieces: &pieces::PieceSet,
+    search_config: &bot::SearchConfig,
+) -> Result<Box<dyn game::Randomizer>, String> {
+    match name {
+        "adversarial" => Ok(Box::new(bot::Adversary::new(
+            pieces.clone(),
+            *search_config,
+        ))),
+        name => game::randomizer::load(name, pieces),
+    }
+}
+
+// removes `--name` from `args`, returning whether it was present
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    match args.iter().position(|arg| arg == name) {
+        Some(index) => {
+            args.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+
+    let mut game_config: game::GameConfig = match take_option(&mut args, "--board") {
+        Some(board) => board.parse().unwrap_or_else(|err| panic!("{}", err)),
+        None => game::GameConfig::default(),
+    };
+
+    if let Some(scoring) = take_option(&mut args, "--scoring") {
+        game_config.scoring = scoring.parse().unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    game_config.hold = take_flag(&mut args, "--hold");
 
-    if args.len() > 1 && args[1] == "--perf" {
+    if let Some(gravity) = take_option(&mut args, "--gravity") {
+        game_config.gravity = gravity.parse().unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    if let Some(interval) = take_option(&mut args, "--garbage") {
+        game_config.garbage_interval = interval.parse().unwrap_or_else(|_| {
+            panic!(
+                "invalid garbage interval '{}', expected an unsigned integer",
+                interval
+            )
+        });
+    }
+
+    let pieces_name = take_option(&mut args, "--pieces").unwrap_or(String::from("pentominoes"));
+
+    let weights = match take_option(&mut args, "--weights") {
+        Some(path) => bot::Weights::from_file(&path).unwrap_or_else(|err| panic!("{}", err)),
+        None => bot::Weights::default(),
+    };
+
+    let search_config = bot::SearchConfig {
+        strategy: match take_option(&mut args, "--search") {
+            Some(strategy) => strategy.parse().unwrap_or_else(|err| panic!("{}", err)),
+            None => bot::Strategy::default(),
+        },
+        expectimax: take_flag(&mut args, "--expectimax"),
+        budget: take_option(&mut args, "--budget")
+            .map(|budget| budget.parse().unwrap_or_else(|err| panic!("{}", err))),
+        threads: match take_option(&mut args, "--threads") {
+            Some(threads) => threads.parse().unwrap_or_else(|_| {
+                panic!(
+                    "invalid thread count '{}', expected an unsigned integer",
+                    threads
+                )
+            }),
+            None => 1,
+        },
+        weights,
+        stats: take_flag(&mut args, "--stats"),
+    };
+
+    let randomizer_name = take_option(&mut args, "--randomizer").unwrap_or(String::from("bag"));
+
+    // random unless given, always printed so any run can be replayed with `--seed`
+    let seed: u64 = match take_option(&mut args, "--seed") {
+        Some(seed) => seed
+            .parse()
+            .unwrap_or_else(|_| panic!("invalid seed '{}', expected an unsigned integer", seed)),
+        None => rand::random(),
+    };
+
+    if args.get(1).map(String::as_str) == Some("--tune") {
+        if args.len() < 3 {
+            panic!("missing value for --tune");
+        }
+
+        let checkpoint_dir = std::path::PathBuf::from(&args[2]);
+
+        let settings = tuner::TuneSettings {
+            generations: match args.get(3) {
+                Some(n) => n.parse().unwrap(),
+                None => DEFAULT_TUNE_GENERATIONS,
+            },
+            population_size: match args.get(4) {
+                Some(n) => n.parse().unwrap(),
+                None => DEFAULT_TUNE_POPULATION_SIZE,
+            },
+            n_games: match args.get(5) {
+                Some(n) => n.parse().unwrap(),
+                None => DEFAULT_TUNE_N_GAMES,
+            },
+            n_searches: match args.get(6) {
+                Some(n) => n.parse().unwrap(),
+                None => DEFAULT_TUNE_N_SEARCHES,
+            },
+            lookahead_size: match args.get(7) {
+                Some(n) => n.parse().unwrap(),
+                None => DEFAULT_TUNE_LOOKAHEAD_SIZE,
+            },
+        };
+
+        let pieces = pieces::PieceSet::load(&pieces_name).unwrap_or_else(|err| panic!("{}", err));
+
+        // fail early on a bad randomizer, instead of once the first game starts
+        load_randomizer(&randomizer_name, &pieces, &search_config)
+            .unwrap_or_else(|err| panic!("{}", err));
+        let new_randomizer = || load_randomizer(&randomizer_name, &pieces, &search_config).unwrap();
+
+        let best = tuner::tune(
+            &settings,
+            &checkpoint_dir,
+            search_config,
+            seed,
+            game_config,
+            &pieces,
+            &new_randomizer,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+        println!("\nbest weights:\n{}", best);
+        println!("saved to: {}", checkpoint_dir.join("best.txt").display());
+        println!("board: {}x{}", game_config.width, game_config.height);
+        println!("pieces: {}", pieces.name);
+        println!("seed: {}", seed);
+    } else if args.len() > 1 && args[1] == "--perf" {
         let n_runs = match args.get(2) {
             Some(n) => n.parse().unwrap(),
             None => DEFAULT_N_RUNS,
@@ -27,26 +195,72 @@ fn main() {
             None => DEFAULT_LOOKAHEAD_SIZE,
         };
 
-        let mut app = app::App::new(lookahead_size);
+        let pieces = pieces::PieceSet::load(&pieces_name).unwrap_or_else(|err| panic!("{}", err));
+        let pieces_name = pieces.name.clone();
+        let randomizer = load_randomizer(&randomizer_name, &pieces, &search_config)
+            .unwrap_or_else(|err| panic!("{}", err));
+
+        let bot: Box<dyn bot::Bot> = Box::new(bot::SearchBot::new(pieces.clone(), search_config));
+
+        let mut app = app::App::new(lookahead_size, game_config, pieces, bot, randomizer, seed);
 
         app.perf_test_bot(n_runs, n_searches, lookahead_size);
 
         println!("\nn_runs: {}", n_runs);
         println!("n_searches: {}", n_searches);
         println!("lookahead_size: {}", lookahead_size);
+        println!("board: {}x{}", game_config.width, game_config.height);
+        println!("hold: {}", game_config.hold);
+        println!("gravity: {}", game_config.gravity);
+        println!("pieces: {}", pieces_name);
     } else {
         let options = eframe::NativeOptions {
-            viewport: egui::ViewportBuilder::default()
-                .with_inner_size(egui::Vec2::new(445.0, 615.0)),
+            viewport: egui::ViewportBuilder::default().with_inner_size(egui::Vec2::new(
+                game_config.width as f32 * ui::SCALE + PANEL_WIDTH,
+                game_config.height as f32 * ui::SCALE + WINDOW_MARGIN,
+            )),
             ..Default::default()
         };
 
         println!("running with lookahead size: {}", DEFAULT_LOOKAHEAD_SIZE);
+        println!(
+            "running with board: {}x{}",
+            game_config.width, game_config.height
+        );
+        println!("running with hold: {}", game_config.hold);
+        println!("running with gravity: {}", game_config.gravity);
+
+        let pieces = pieces::PieceSet::load(&pieces_name).unwrap_or_else(|err| panic!("{}", err));
+        println!("running with pieces: {}", pieces.name);
+
+        let search_config = bot::SearchConfig {
+            budget: search_config.budget.or(Some(DEFAULT_GUI_BUDGET)),
+            // shown under "Last search"
+            stats: true,
+            ..search_config
+        };
+        let randomizer = load_randomizer(&randomizer_name, &pieces, &search_config)
+            .unwrap_or_else(|err| panic!("{}", err));
+        println!("running with randomizer: {}", randomizer.name());
+        let bot: Box<dyn bot::Bot> = Box::new(
+            bot::SearchBot::new(pieces.clone(), search_config).with_ranking(app::TOP_PLACEMENTS),
+        );
+        println!("running with bot: {}", bot.name());
+        println!("running with seed: {}", seed);
 
         let _ = eframe::run_native(
             "Tetrs",
             options,
-            Box::new(|_creation_ctx| Box::new(app::App::new(DEFAULT_LOOKAHEAD_SIZE))),
+            Box::new(move |_creation_ctx| {
+                Box::new(app::App::new(
+                    DEFAULT_LOOKAHEAD_SIZE,
+                    game_config,
+                    pieces,
+                    bot,
+                    randomizer,
+                    seed,
+                ))
+            }),
         );
     }
 }
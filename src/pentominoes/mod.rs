@@ -1,77 +1,116 @@
-pub type Shape = Vec<Vec<u8>>;
-pub type Permutations = Vec<Vec<Shape>>;
-
-fn print_mutations(permutations: &Permutations) {
-    for (pent_id, _) in permutations.iter().enumerate() {
-        println!("{}", permutations[pent_id].len());
-
-        for permutation in 0..permutations[pent_id].len() {
-            println!("ID: {}", pent_id);
-            println!("permutation: {}", permutation);
-
-            for y in 0..permutations[pent_id][permutation][0].len() {
-                for x in 0..permutations[pent_id][permutation].len() {
-                    print!("{}", permutations[pent_id][permutation][x][y]);
-                }
-                println!();
+use crate::pieces::{self, Permutations, Shape};
+
+// symbol of each pentomino, indexed by pentomino ID
+pub const SYMBOLS: [char; 12] = ['X', 'I', 'Z', 'T', 'U', 'V', 'W', 'Y', 'L', 'P', 'N', 'F'];
+
+// parses the base shape of each pentomino from `shapes.txt`, indexed by pentomino ID
+pub fn load_base_shapes() -> Vec<Shape> {
+    let mut shapes: Vec<Shape> = Vec::new();
+
+    // loaded at compile time, hence no runtime file-reading overhead
+    let txt = include_str!("shapes.txt");
+
+    for line in txt.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if let Some(id) = line.strip_prefix("ID:") {
+            let pent_id: usize = id.trim().parse().unwrap();
+
+            if pent_id >= shapes.len() {
+                shapes.resize_with(pent_id + 1, Vec::new);
             }
-            println!();
+
+            continue;
         }
-    }
-}
 
-pub fn char_to_id(c: char) -> u8 {
-    match c {
-        'X' => 0,
-        'I' => 1,
-        'Z' => 2,
-        'T' => 3,
-        'U' => 4,
-        'V' => 5,
-        'W' => 6,
-        'Y' => 7,
-        'L' => 8,
-        'P' => 9,
-        'N' => 10,
-        'F' => 11,
-        _ => 255,
+        let row = line.bytes().map(|cell| cell - b'0').collect();
+
+        shapes
+            .last_mut()
+            .expect("shape row before any ID line")
+            .push(row);
     }
+
+    shapes
 }
 
 // returns 4D vec:
 // 1st D: pentomino ID, 2nd D: permutation ID, 3rd D: permutation as 2d vec
+// pieces are one-sided: rotations only, mirror images are not playable
 pub fn load_permutations() -> Permutations {
-    let mut pentominoes = Vec::<Vec<Vec<Vec<u8>>>>::default();
+    load_base_shapes()
+        .iter()
+        .map(|shape| pieces::generate_permutations(shape, false))
+        .collect()
+}
 
-    // small csv file, hence small allocation
-    // loaded at compile time, hence no runtime file-reading overhead
-    let csv = include_str!("pentomino_db.csv");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    for line in csv.lines() {
-        let mutation_data: Vec<u8> = line.split(',').map(|s| s.parse().unwrap()).collect();
+    // the hand-written permutations that predate `generate_permutations()`
+    fn load_csv_permutations() -> Permutations {
+        let mut pentominoes = Vec::<Vec<Vec<Vec<u8>>>>::default();
 
-        let pent_id = mutation_data[0] as usize;
-        let permutation = mutation_data[1] as usize;
-        let x_size = mutation_data[2] as usize;
-        let y_size = mutation_data[3] as usize;
+        let csv = include_str!("pentomino_db.csv");
 
-        let mut piece = vec![vec![0u8; y_size]; x_size];
+        for line in csv.lines() {
+            let mutation_data: Vec<u8> = line.split(',').map(|s| s.parse().unwrap()).collect();
 
-        // 1d array to 2d array
-        for i in 0..(x_size * y_size) {
-            piece[i / y_size][i % y_size] = mutation_data[4 + i];
-        }
+            let pent_id = mutation_data[0] as usize;
+            let permutation = mutation_data[1] as usize;
+            let x_size = mutation_data[2] as usize;
+            let y_size = mutation_data[3] as usize;
 
-        if pent_id >= pentominoes.len() {
-            pentominoes.resize_with(pent_id + 1, Vec::new);
-        }
+            let mut piece = vec![vec![0u8; y_size]; x_size];
 
-        if permutation >= pentominoes[pent_id].len() {
-            pentominoes[pent_id].resize_with(permutation + 1, Vec::new);
+            // 1d array to 2d array
+            for i in 0..(x_size * y_size) {
+                piece[i / y_size][i % y_size] = mutation_data[4 + i];
+            }
+
+            if pent_id >= pentominoes.len() {
+                pentominoes.resize_with(pent_id + 1, Vec::new);
+            }
+
+            if permutation >= pentominoes[pent_id].len() {
+                pentominoes[pent_id].resize_with(permutation + 1, Vec::new);
+            }
+
+            pentominoes[pent_id][permutation] = piece;
         }
 
-        pentominoes[pent_id][permutation] = piece;
+        pentominoes
     }
 
-    pentominoes
+    #[test]
+    fn test_generated_permutations_match_csv() {
+        let generated = load_permutations();
+        let csv = load_csv_permutations();
+
+        assert_eq!(generated.len(), 12);
+        assert_eq!(generated.len(), csv.len());
+
+        for (pent_id, (generated, csv)) in generated.iter().zip(&csv).enumerate() {
+            assert_eq!(generated.len(), csv.len(), "pentomino {}", pent_id);
+
+            for shape in csv {
+                assert!(
+                    generated.contains(shape),
+                    "pentomino {}: {:?}",
+                    pent_id,
+                    shape
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_mirrored_permutations() {
+        let counts: Vec<usize> = load_base_shapes()
+            .iter()
+            .map(|shape| pieces::generate_permutations(shape, true).len())
+            .collect();
+
+        // the 63 fixed pentominoes
+        assert_eq!(counts, vec![1, 2, 4, 4, 4, 4, 4, 8, 8, 8, 8, 8]);
+    }
 }
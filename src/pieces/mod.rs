@@ -0,0 +1,431 @@
+use crate::{game, pentominoes};
+
+use std::collections::HashSet;
+
+pub type Shape = Vec<Vec<u8>>;
+pub type Permutations = Vec<Vec<Shape>>;
+
+// piece ids are stored in 8 bits of a composite_id, and 255 is reserved for garbage,
+// see `game::garbage::GARBAGE_PIECE_ID`
+// `game::EMPTY` has piece id 255 too, it only stays apart from garbage tiles as its
+// unique id 0xFFFFFF is never handed out, see `IdManager::next_unique_id()`
+pub const MAX_PIECES: usize = 255;
+
+// debugging helper, kept around for inspecting loaded permutations
+#[allow(dead_code)]
+pub fn print_mutations(permutations: &Permutations) {
+    for (piece_id, piece_permutations) in permutations.iter().enumerate() {
+        println!("{}", piece_permutations.len());
+
+        for (permutation, shape) in piece_permutations.iter().enumerate() {
+            println!("ID: {}", piece_id);
+            println!("permutation: {}", permutation);
+
+            for y in 0..shape[0].len() {
+                for shape_row in shape {
+                    print!("{}", shape_row[y]);
+                }
+                println!();
+            }
+            println!();
+        }
+    }
+}
+
+const HEXOMINO_SYMBOLS: &str = "abcdefghijklmnopqrstuvwxyz012345678";
+
+#[derive(Clone)]
+pub struct Piece {
+    pub symbol: char,
+    pub moves: game::PieceMoves,
+}
+
+impl Piece {
+    pub fn new(symbol: char, base_shape: &[Vec<u8>], mirror: bool) -> Self {
+        Self::from_permutations(symbol, generate_permutations(base_shape, mirror))
+    }
+
+    pub fn from_permutations(symbol: char, permutations: Vec<Shape>) -> Self {
+        Piece {
+            symbol,
+            moves: game::PieceMoves::new(&permutations),
+        }
+    }
+}
+
+// the pieces a game is played with, indexed by piece id
+#[derive(Clone)]
+pub struct PieceSet {
+    pub name: String,
+    pub pieces: Vec<Piece>,
+}
+
+impl PieceSet {
+    // a preset name, or the path to a piece file otherwise
+    pub fn load(name: &str) -> Result<Self, String> {
+        match name {
+            "tetrominoes" => Ok(Self::tetrominoes()),
+            "pentominoes" => Ok(Self::pentominoes()),
+            "hexominoes" => Ok(Self::hexominoes()),
+            path => Self::from_file(path),
+        }
+    }
+
+    // the 7 classic tetrominoes
+    pub fn tetrominoes() -> Self {
+        Self::parse("tetrominoes", include_str!("tetrominoes.txt"))
+            .expect("invalid built-in tetrominoes.txt")
+    }
+
+    // the 12 pentominoes
+    pub fn pentominoes() -> Self {
+        let pieces = pentominoes::load_permutations()
+            .into_iter()
+            .zip(pentominoes::SYMBOLS)
+            .map(|(permutations, symbol)| Piece::from_permutations(symbol, permutations))
+            .collect();
+
+        PieceSet {
+            name: String::from("pentominoes"),
+            pieces,
+        }
+    }
+
+    // the 35 free hexominoes
+    pub fn hexominoes() -> Self {
+        let pieces = enumerate_polyominoes(6)
+            .iter()
+            .zip(HEXOMINO_SYMBOLS.chars())
+            .map(|(shape, symbol)| Piece::new(symbol, shape, false))
+            .collect();
+
+        PieceSet {
+            name: String::from("hexominoes"),
+            pieces,
+        }
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("could not read piece file '{}': {}", path, err))?;
+
+        Self::parse(path, &text)
+    }
+
+    // piece file format, see `tetrominoes.txt` for an example:
+    // - `<symbol>:` starts a new piece, followed by its rows of 0s and 1s
+    // - `mirror` makes mirror images playable, for all pieces in the file
+    // - blank lines and lines starting with '#' are ignored
+    pub fn parse(name: &str, text: &str) -> Result<Self, String> {
+        let mut mirror = false;
+        let mut shapes: Vec<(char, Shape)> = Vec::new();
+
+        for (line_number, line) in text.lines().enumerate().map(|(i, l)| (i + 1, l.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line == "mirror" {
+                mirror = true;
+                continue;
+            }
+
+            if let Some(symbol) = line.strip_suffix(':') {
+                let mut chars = symbol.trim().chars();
+
+                let symbol = match (chars.next(), chars.next()) {
+                    (Some(symbol), None) => symbol,
+                    _ => {
+                        return Err(format!(
+                            "{}:{}: piece symbol must be a single character",
+                            name, line_number
+                        ))
+                    }
+                };
+
+                if shapes.iter().any(|(other, _)| *other == symbol) {
+                    return Err(format!(
+                        "{}:{}: duplicate piece symbol '{}'",
+                        name, line_number, symbol
+                    ));
+                }
+
+                shapes.push((symbol, Vec::new()));
+                continue;
+            }
+
+            let row = line
+                .chars()
+                .map(|cell| match cell {
+                    '0' => Ok(0),
+                    '1' => Ok(1),
+                    _ => Err(format!(
+                        "{}:{}: expected a row of 0s and 1s",
+                        name, line_number
+                    )),
+                })
+                .collect::<Result<Vec<u8>, String>>()?;
+
+            let (_, shape) = shapes
+                .last_mut()
+                .ok_or(format!("{}:{}: row before any piece", name, line_number))?;
+            shape.push(row);
+        }
+
+        if shapes.is_empty() || shapes.len() > MAX_PIECES {
+            return Err(format!(
+                "{}: expected between 1 and {} pieces, got {}",
+                name,
+                MAX_PIECES,
+                shapes.len()
+            ));
+        }
+
+        let mut pieces = Vec::with_capacity(shapes.len());
+
+        for (symbol, shape) in shapes {
+            if shape.iter().any(|row| row.len() != shape[0].len()) {
+                return Err(format!(
+                    "{}: rows of piece '{}' differ in length",
+                    name, symbol
+                ));
+            }
+
+            let shape = trim(&shape);
+
+            if shape.is_empty() {
+                return Err(format!("{}: piece '{}' has no tiles", name, symbol));
+            }
+
+            pieces.push(Piece::new(symbol, &shape, mirror));
+        }
+
+        // ids go up to `pieces.len() - 1`
+        assert!(
+            pieces.len() <= game::garbage::GARBAGE_PIECE_ID as usize,
+            "a piece would get the garbage piece id"
+        );
+
+        Ok(PieceSet {
+            name: String::from(name),
+            pieces,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.pieces.len()
+    }
+
+    pub fn symbol(&self, piece_id: u8) -> char {
+        self.pieces
+            .get(piece_id as usize)
+            .map_or('?', |piece| piece.symbol)
+    }
+
+    pub fn id_of(&self, symbol: char) -> Option<u8> {
+        self.pieces
+            .iter()
+            .position(|piece| piece.symbol == symbol)
+            .map(|piece_id| piece_id as u8)
+    }
+
+    pub fn moves(&self, piece_id: u8) -> &game::PieceMoves {
+        &self.pieces[piece_id as usize].moves
+    }
+}
+
+impl Default for PieceSet {
+    fn default() -> Self {
+        Self::pentominoes()
+    }
+}
+
+pub fn rotate_cw(shape: &[Vec<u8>]) -> Shape {
+    let (height, width) = (shape.len(), shape[0].len());
+
+    (0..width)
+        .map(|row| {
+            (0..height)
+                .map(|col| shape[height - 1 - col][row])
+                .collect()
+        })
+        .collect()
+}
+
+pub fn rotate_ccw(shape: &[Vec<u8>]) -> Shape {
+    let (height, width) = (shape.len(), shape[0].len());
+
+    (0..width)
+        .map(|row| (0..height).map(|col| shape[col][width - 1 - row]).collect())
+        .collect()
+}
+
+// mirrors horizontally
+pub fn flip(shape: &[Vec<u8>]) -> Shape {
+    shape
+        .iter()
+        .map(|row| row.iter().rev().copied().collect())
+        .collect()
+}
+
+// drops empty rows and columns around the shape
+fn trim(shape: &[Vec<u8>]) -> Shape {
+    let filled = |row: &Vec<u8>| row.iter().any(|&cell| cell != 0);
+    let rows: Vec<&Vec<u8>> = shape.iter().filter(|row| filled(row)).collect();
+
+    let cols: Vec<usize> = (0..shape.first().map_or(0, |row| row.len()))
+        .filter(|&col| rows.iter().any(|row| row[col] != 0))
+        .collect();
+
+    rows.iter()
+        .map(|row| cols.iter().map(|&col| row[col]).collect())
+        .collect()
+}
+
+// every distinct rotation of `shape`, starting with `shape` itself
+// with `mirror`, followed by the rotations of its mirror image
+pub fn generate_permutations(shape: &[Vec<u8>], mirror: bool) -> Vec<Shape> {
+    let mut permutations: Vec<Shape> = Vec::with_capacity(8);
+
+    let mut starts = vec![shape.to_vec()];
+    if mirror {
+        starts.push(flip(shape));
+    }
+
+    for start in starts {
+        let mut current = start;
+
+        for _ in 0..4 {
+            // shapes are always trimmed to their bounding box,
+            // so equal matrices means equal orientations
+            if !permutations.contains(&current) {
+                permutations.push(current.clone());
+            }
+
+            current = rotate_cw(&current);
+        }
+    }
+
+    permutations
+}
+
+// all free polyominoes of `size` tiles, grown one tile at a time from a single tile
+// and deduplicated by their canonical form under rotation and reflection
+pub fn enumerate_polyominoes(size: usize) -> Vec<Shape> {
+    let mut polyominoes: Vec<Vec<(i32, i32)>> = vec![vec![(0, 0)]];
+
+    for _ in 1..size {
+        let mut seen = HashSet::new();
+        let mut grown = Vec::new();
+
+        for tiles in &polyominoes {
+            for &(row, col) in tiles {
+                for (delta_row, delta_col) in [(-1, 0), (0, 1), (1, 0), (0, -1)] {
+                    let tile = (row + delta_row, col + delta_col);
+
+                    if tiles.contains(&tile) {
+                        continue;
+                    }
+
+                    let mut candidate = tiles.clone();
+                    candidate.push(tile);
+
+                    let candidate = canonical_form(&candidate);
+
+                    if seen.insert(candidate.clone()) {
+                        grown.push(candidate);
+                    }
+                }
+            }
+        }
+
+        polyominoes = grown;
+    }
+
+    polyominoes
+        .iter()
+        .map(|tiles| tiles_to_shape(tiles))
+        .collect()
+}
+
+type Transform = fn((i32, i32)) -> (i32, i32);
+
+// smallest sorted tile list among all 8 rotations/reflections, shifted to the origin
+fn canonical_form(tiles: &[(i32, i32)]) -> Vec<(i32, i32)> {
+    let transforms: [Transform; 8] = [
+        |(r, c)| (r, c),
+        |(r, c)| (c, -r),
+        |(r, c)| (-r, -c),
+        |(r, c)| (-c, r),
+        |(r, c)| (r, -c),
+        |(r, c)| (-c, -r),
+        |(r, c)| (-r, c),
+        |(r, c)| (c, r),
+    ];
+
+    transforms
+        .iter()
+        .map(|transform| {
+            let moved: Vec<(i32, i32)> = tiles.iter().map(|&tile| transform(tile)).collect();
+
+            let min_row = moved.iter().map(|&(r, _)| r).min().unwrap_or(0);
+            let min_col = moved.iter().map(|&(_, c)| c).min().unwrap_or(0);
+
+            let mut moved: Vec<(i32, i32)> = moved
+                .iter()
+                .map(|&(r, c)| (r - min_row, c - min_col))
+                .collect();
+            moved.sort();
+            moved
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+fn tiles_to_shape(tiles: &[(i32, i32)]) -> Shape {
+    let height = tiles.iter().map(|&(r, _)| r).max().unwrap_or(0) as usize + 1;
+    let width = tiles.iter().map(|&(_, c)| c).max().unwrap_or(0) as usize + 1;
+
+    let mut shape = vec![vec![0; width]; height];
+
+    for &(row, col) in tiles {
+        shape[row as usize][col as usize] = 1;
+    }
+
+    shape
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets() {
+        assert_eq!(PieceSet::tetrominoes().len(), 7);
+        assert_eq!(PieceSet::pentominoes().len(), 12);
+        assert_eq!(PieceSet::hexominoes().len(), 35);
+
+        // the 12 free pentominoes
+        assert_eq!(enumerate_polyominoes(5).len(), 12);
+    }
+
+    #[test]
+    fn test_parse() {
+        let pieces =
+            PieceSet::parse("test", "# comment\nmirror\nS:\n011\n110\n\nO:\n11\n11\n").unwrap();
+
+        assert_eq!(pieces.len(), 2);
+        assert_eq!(pieces.symbol(0), 'S');
+        assert_eq!(pieces.symbol(1), 'O');
+        // 2 rotations, each with its mirror image
+        assert_eq!(pieces.moves(0).shapes.len(), 4);
+        assert_eq!(pieces.moves(1).shapes.len(), 1);
+
+        assert!(PieceSet::parse("test", "").is_err());
+        assert!(PieceSet::parse("test", "11\n").is_err());
+        assert!(PieceSet::parse("test", "A:\n12\n").is_err());
+        assert!(PieceSet::parse("test", "A:\n1\nA:\n1\n").is_err());
+        assert!(PieceSet::parse("test", "A:\n11\n1\n").is_err());
+        assert!(PieceSet::parse("test", "A:\n00\n").is_err());
+    }
+}
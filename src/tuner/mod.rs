@@ -0,0 +1,375 @@
+use crate::{app, bot, game, pieces};
+
+use std::path::Path;
+use std::str::FromStr;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// best candidates carried over unchanged to the next generation
+const ELITE_COUNT: usize = 2;
+const TOURNAMENT_SIZE: usize = 3;
+// chance of each weight being mutated, and the size of a mutation relative to the weight
+const MUTATION_RATE: f64 = 0.2;
+const MUTATION_SCALE: f64 = 0.3;
+// weights this small still mutate by a useful amount, and can change sign
+const MIN_MUTATION: f64 = 0.05;
+
+// files written to the checkpoint directory
+const POPULATION_FILE: &str = "population.txt";
+const BEST_FILE: &str = "best.txt";
+
+pub struct TuneSettings {
+    pub generations: u32,
+    pub population_size: usize,
+    // games each candidate plays per generation, all candidates play the same games
+    pub n_games: u32,
+    pub n_searches: u32,
+    pub lookahead_size: u8,
+}
+
+// the population about to be evaluated, along with what is needed to carry on from it
+#[derive(Debug, PartialEq)]
+pub struct Checkpoint {
+    pub seed: u64,
+    pub generation: u32,
+    pub population: Vec<bot::Weights>,
+}
+
+impl Checkpoint {
+    // `initial` and random mutations of it
+    pub fn new(initial: bot::Weights, seed: u64, population_size: usize) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let population = (0..population_size)
+            .map(|i| match i {
+                0 => initial,
+                _ => mutate(&initial, 1.0, &mut rng),
+            })
+            .collect();
+
+        Checkpoint {
+            seed,
+            generation: 0,
+            population,
+        }
+    }
+
+    fn load(path: &Path) -> Result<Option<Self>, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => text
+                .parse()
+                .map(Some)
+                .map_err(|err| format!("{}: {}", path.display(), err)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(format!("could not read '{}': {}", path.display(), err)),
+        }
+    }
+}
+
+// a `key = value` header, then each candidate in the `bot::Weights` format,
+// every one of them preceded by a `---` line
+impl FromStr for Checkpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut sections = s.split("\n---\n");
+
+        let header = sections.next().unwrap_or_default();
+        let mut seed = None;
+        let mut generation = None;
+
+        for line in header.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            match line.split_once('=').map(|(k, v)| (k.trim(), v.trim())) {
+                Some(("seed", value)) => seed = value.parse().ok(),
+                Some(("generation", value)) => generation = value.parse().ok(),
+                _ => return Err(format!("invalid checkpoint header line '{}'", line)),
+            }
+        }
+
+        let population = sections
+            .map(str::parse)
+            .collect::<Result<Vec<bot::Weights>, String>>()?;
+
+        if population.is_empty() {
+            return Err(String::from("checkpoint has no candidates"));
+        }
+
+        Ok(Checkpoint {
+            seed: seed.ok_or("checkpoint is missing a valid seed")?,
+            generation: generation.ok_or("checkpoint is missing a valid generation")?,
+            population,
+        })
+    }
+}
+
+impl std::fmt::Display for Checkpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "seed = {}", self.seed)?;
+        write!(f, "generation = {}", self.generation)?;
+
+        for weights in &self.population {
+            write!(f, "\n---\n{}", weights)?;
+        }
+
+        Ok(())
+    }
+}
+
+// evolves heuristic weights with a genetic algorithm, scoring each candidate by its
+// average score over seeded games played through `App::play_game()`
+// starts from the weights of `search_config`, or resumes from the checkpoint in `dir`
+// if there is one, returns the best weights found
+pub fn tune(
+    settings: &TuneSettings,
+    dir: &Path,
+    search_config: bot::SearchConfig,
+    seed: u64,
+    game_config: game::GameConfig,
+    pieces: &pieces::PieceSet,
+    new_randomizer: &dyn Fn() -> Box<dyn game::Randomizer>,
+) -> Result<bot::Weights, String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|err| format!("could not create '{}': {}", dir.display(), err))?;
+
+    let population_path = dir.join(POPULATION_FILE);
+
+    let mut checkpoint = match Checkpoint::load(&population_path)? {
+        Some(checkpoint) => {
+            println!(
+                "resuming from generation {}, seed {}",
+                checkpoint.generation, checkpoint.seed
+            );
+            checkpoint
+        }
+        None => Checkpoint::new(search_config.weights, seed, settings.population_size),
+    };
+
+    let mut best = checkpoint.population[0];
+
+    while checkpoint.generation < settings.generations {
+        // derived from the generation, so a resumed run plays out like an uninterrupted one
+        let mut rng =
+            StdRng::seed_from_u64(checkpoint.seed.wrapping_add(checkpoint.generation as u64));
+        let game_seeds: Vec<u64> = (0..settings.n_games).map(|_| rng.gen()).collect();
+
+        let fitness: Vec<f64> = checkpoint
+            .population
+            .iter()
+            .map(|&weights| {
+                let search_config = bot::SearchConfig {
+                    weights,
+                    ..search_config
+                };
+                evaluate(
+                    &search_config,
+                    &game_seeds,
+                    settings,
+                    game_config,
+                    pieces,
+                    new_randomizer,
+                )
+            })
+            .collect();
+
+        // best first
+        let mut ranking: Vec<usize> = (0..fitness.len()).collect();
+        ranking.sort_by(|&a, &b| fitness[b].total_cmp(&fitness[a]));
+
+        best = checkpoint.population[ranking[0]];
+
+        println!(
+            "generation {}: best {:.2}, mean {:.2}",
+            checkpoint.generation + 1,
+            fitness[ranking[0]],
+            fitness.iter().sum::<f64>() / fitness.len() as f64
+        );
+
+        write(
+            &dir.join(BEST_FILE),
+            &format!(
+                "# generation {}, avg score {:.2}\n{}",
+                checkpoint.generation + 1,
+                fitness[ranking[0]],
+                best
+            ),
+        )?;
+
+        checkpoint.population = breed(&checkpoint.population, &ranking, &mut rng);
+        checkpoint.generation += 1;
+
+        write(&population_path, &checkpoint.to_string())?;
+    }
+
+    Ok(best)
+}
+
+fn evaluate(
+    search_config: &bot::SearchConfig,
+    game_seeds: &[u64],
+    settings: &TuneSettings,
+    game_config: game::GameConfig,
+    pieces: &pieces::PieceSet,
+    new_randomizer: &dyn Fn() -> Box<dyn game::Randomizer>,
+) -> f64 {
+    let total_score: u64 = game_seeds
+        .iter()
+        .map(|&seed| {
+            let mut app = app::App::new(
+                settings.lookahead_size,
+                game_config,
+                pieces.clone(),
+                Box::new(bot::SearchBot::new(pieces.clone(), *search_config)),
+                new_randomizer(),
+                seed,
+            );
+
+            let (state, _) = app.play_game(settings.n_searches, settings.lookahead_size);
+            state.score
+        })
+        .sum();
+
+    total_score as f64 / game_seeds.len().max(1) as f64
+}
+
+// elites as they are, then children of tournament-selected parents
+fn breed(population: &[bot::Weights], ranking: &[usize], rng: &mut StdRng) -> Vec<bot::Weights> {
+    let mut next: Vec<bot::Weights> = ranking
+        .iter()
+        .take(ELITE_COUNT)
+        .map(|&i| population[i])
+        .collect();
+
+    while next.len() < population.len() {
+        let a = select(population, ranking, rng);
+        let b = select(population, ranking, rng);
+
+        let child = crossover(a, b, rng);
+        next.push(mutate(&child, MUTATION_RATE, rng));
+    }
+
+    next
+}
+
+// the best of a few random candidates, `ranking` is best first
+fn select<'a>(
+    population: &'a [bot::Weights],
+    ranking: &[usize],
+    rng: &mut StdRng,
+) -> &'a bot::Weights {
+    let best_rank = (0..TOURNAMENT_SIZE)
+        .map(|_| rng.gen_range(0..ranking.len()))
+        .min()
+        .unwrap_or(0);
+
+    &population[ranking[best_rank]]
+}
+
+// a random blend of both parents, weight by weight
+fn crossover(a: &bot::Weights, b: &bot::Weights, rng: &mut StdRng) -> bot::Weights {
+    let (a, b) = (a.to_array(), b.to_array());
+
+    bot::Weights::from_array(std::array::from_fn(|i| {
+        let t: f64 = rng.gen();
+        a[i] + t * (b[i] - a[i])
+    }))
+}
+
+fn mutate(weights: &bot::Weights, rate: f64, rng: &mut StdRng) -> bot::Weights {
+    bot::Weights::from_array(weights.to_array().map(|weight| {
+        if !rng.gen_bool(rate) {
+            return weight;
+        }
+
+        let scale = (weight.abs() * MUTATION_SCALE).max(MIN_MUTATION);
+        weight + rng.gen_range(-scale..scale)
+    }))
+}
+
+// written to a temporary file first, so an interrupted run never leaves a truncated checkpoint
+fn write(path: &Path, contents: &str) -> Result<(), String> {
+    let temporary = path.with_extension("tmp");
+
+    std::fs::write(&temporary, contents)
+        .and_then(|_| std::fs::rename(&temporary, path))
+        .map_err(|err| format!("could not write '{}': {}", path.display(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_format() {
+        let checkpoint = Checkpoint::new(bot::Weights::default(), 7, 4);
+
+        assert_eq!(checkpoint.population.len(), 4);
+        assert_eq!(checkpoint.population[0], bot::Weights::default());
+
+        let parsed: Checkpoint = checkpoint.to_string().parse().unwrap();
+        assert_eq!(parsed, checkpoint);
+
+        assert!("generation = 1\n---\nholes = 1"
+            .parse::<Checkpoint>()
+            .is_err());
+        assert!("seed = 1\ngeneration = 1".parse::<Checkpoint>().is_err());
+    }
+
+    #[test]
+    fn test_resume() {
+        let pieces = pieces::PieceSet::pentominoes();
+        let new_randomizer = || game::randomizer::load("bag", &pieces).unwrap();
+
+        let mut settings = TuneSettings {
+            generations: 1,
+            population_size: 4,
+            n_games: 1,
+            n_searches: 10,
+            lookahead_size: 2,
+        };
+
+        let dir = std::env::temp_dir().join(format!("tetrs-tune-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let tune_in = |dir: &Path, settings: &TuneSettings| {
+            tune(
+                settings,
+                dir,
+                bot::SearchConfig::default(),
+                3,
+                game::GameConfig::default(),
+                &pieces,
+                &new_randomizer,
+            )
+            .unwrap()
+        };
+
+        tune_in(&dir, &settings);
+
+        let checkpoint = Checkpoint::load(&dir.join(POPULATION_FILE))
+            .unwrap()
+            .unwrap();
+        assert_eq!(checkpoint.generation, 1);
+        assert_eq!(checkpoint.population.len(), 4);
+
+        // picks up at generation 1 instead of starting over
+        settings.generations = 2;
+        tune_in(&dir, &settings);
+
+        let resumed = Checkpoint::load(&dir.join(POPULATION_FILE))
+            .unwrap()
+            .unwrap();
+        assert_eq!(resumed.generation, 2);
+
+        // same as running both generations in one go
+        let uninterrupted = dir.join("uninterrupted");
+        tune_in(&uninterrupted, &settings);
+
+        let expected = Checkpoint::load(&uninterrupted.join(POPULATION_FILE))
+            .unwrap()
+            .unwrap();
+        assert_eq!(resumed, expected);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
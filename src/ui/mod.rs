@@ -1,16 +1,13 @@
-use core::panic;
-use std::time::Duration;
-
-use crate::{app, game};
+use crate::{bot, game};
 
 pub const SCALE: f32 = 40.0;
 
 pub fn draw_game_field(ui: &mut egui::Ui, field: &game::GameField) {
+    let height = field.height();
+    let width = field.width();
+
     let (response, painter) = ui.allocate_painter(
-        egui::Vec2::new(
-            game::FIELD_WIDTH as f32 * SCALE,
-            game::FIELD_HEIGHT as f32 * SCALE,
-        ),
+        egui::Vec2::new(width as f32 * SCALE, height as f32 * SCALE),
         egui::Sense::hover(),
     );
 
@@ -23,15 +20,13 @@ pub fn draw_game_field(ui: &mut egui::Ui, field: &game::GameField) {
     // https://rust-lang.github.io/rust-clippy/master/index.html#needless_range_loop
     // before: for row in 0..game::FIELD_HEIGHT {
     // after:
-    for (row, _) in field.iter().enumerate().take(game::FIELD_HEIGHT) {
-        for (col, _) in field[row].iter().enumerate().take(game::FIELD_WIDTH) {
-            let tile = field[row][col];
-
+    for (row, tiles) in field.rows().enumerate() {
+        for (col, &tile) in tiles.iter().enumerate() {
             if tile == game::EMPTY {
                 continue;
             }
 
-            let color = get_pent_color(game::get_pent_id(tile));
+            let color = get_piece_color(game::get_piece_id(tile));
             let spacing = 3.0;
 
             let x = draw_area.left() + col as f32 * SCALE;
@@ -49,17 +44,39 @@ pub fn draw_game_field(ui: &mut egui::Ui, field: &game::GameField) {
     }
 }
 
+// what the bot has to say about its last search
+pub struct BotPanel<'a> {
+    pub ema_solution_time_ms: f64,
+    pub search_stats: Option<&'a bot::SearchStats>,
+    pub ranking: &'a [bot::RankedPlacement],
+    // the ranked placement under the mouse, shown on the board instead of the frame
+    pub hovered_placement: &'a mut Option<usize>,
+}
+
 pub fn draw_ui(
     ui: &mut egui::Ui,
     frame_to_draw: &game::GameField,
     delay_ms: &mut u16,
-    cleared_rows: u32,
-    ema_solution_time_ms: f64,
+    game_state: &game::State,
+    bot_panel: BotPanel,
     is_bot_paused: &mut bool,
+    restart_requested: &mut bool,
 ) {
+    let BotPanel {
+        ema_solution_time_ms,
+        search_stats,
+        ranking,
+        hovered_placement,
+    } = bot_panel;
+
     ui.horizontal(|ui| {
         // left side
-        draw_game_field(ui, frame_to_draw);
+        // hovering is only known once the panel is drawn, so a frame late
+        match hovered_placement.and_then(|i| ranking.get(i)) {
+            Some(placement) => draw_game_field(ui, &placement.field),
+            None => draw_game_field(ui, frame_to_draw),
+        }
+        *hovered_placement = None;
 
         // right side
         ui.vertical(|ui| {
@@ -70,7 +87,17 @@ pub fn draw_ui(
 
             ui.add_space(20.0);
 
-            ui.label(format!("Cleared rows:  {}", cleared_rows));
+            ui.label(format!("Score:  {}", game_state.score));
+            ui.label(format!("Level:  {}", game_state.level()));
+            ui.label(format!("Combo:  {}", game_state.combo));
+
+            ui.add_space(20.0);
+
+            ui.label(format!("Cleared rows:  {}", game_state.cleared_rows));
+
+            ui.add_space(20.0);
+
+            ui.label(format!("Pieces placed:  {}", game_state.pieces_placed));
 
             ui.add_space(20.0);
 
@@ -79,16 +106,109 @@ pub fn draw_ui(
                 ema_solution_time_ms
             ));
 
+            // of the last search, collapsed as the panel is only as tall as the board
+            if let Some(stats) = search_stats {
+                egui::CollapsingHeader::new("Last search").show(ui, |ui| {
+                    ui.label(format!("Nodes expanded:  {}", stats.nodes_expanded));
+                    ui.label(format!(
+                        "Branching factor:  {:.2}",
+                        stats.branching_factor()
+                    ));
+                    ui.label(format!("Transposition hits:  {}", stats.transposition_hits));
+                    ui.label(format!("Max frontier:  {}", stats.max_frontier));
+
+                    for (depth, time) in stats.time_per_depth.iter().enumerate() {
+                        ui.label(format!(
+                            "Depth {}:  {:.3} ms",
+                            depth,
+                            time.as_secs_f64() * 1000.0
+                        ));
+                    }
+                });
+            }
+
+            // the one played, then the best of the others
+            // hover one to see it on the board, and why it scored what it did
+            if !ranking.is_empty() {
+                egui::CollapsingHeader::new("Top placements")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        for (i, placement) in ranking.iter().enumerate() {
+                            let played = match placement.played {
+                                true => "  (played)",
+                                false => "",
+                            };
+
+                            let response = ui
+                                .label(format!(
+                                    "{}.  {}  {:.2}{}",
+                                    i + 1,
+                                    placement.symbol,
+                                    placement.value,
+                                    played
+                                ))
+                                .on_hover_ui(|ui| {
+                                    let target = &placement.placement;
+                                    ui.label(format!(
+                                        "permutation {}, row {}, col {}",
+                                        target.permutation, target.row, target.col
+                                    ));
+                                    if target.hold {
+                                        ui.label("after hold");
+                                    }
+                                    ui.separator();
+
+                                    // feature value, then its weighted share of the evaluation
+                                    let values = placement.features.to_array();
+
+                                    for ((feature, contribution), value) in
+                                        placement.contributions.iter().zip(values)
+                                    {
+                                        ui.label(format!(
+                                            "{}:  {}  ({:+.2})",
+                                            feature, value, contribution
+                                        ));
+                                    }
+                                });
+
+                            if response.hovered() {
+                                *hovered_placement = Some(i);
+                            }
+                        }
+                    });
+            }
+
             ui.add_space(20.0);
 
             if ui.button("Pause | Continue").clicked() {
                 *is_bot_paused = !*is_bot_paused;
             }
+
+            if game_state.status == game::GameStatus::GameOver {
+                ui.add_space(20.0);
+
+                ui.label(
+                    egui::RichText::new("GAME OVER")
+                        .size(24.0)
+                        .color(egui::Color32::RED),
+                );
+
+                ui.label(format!(
+                    "Topped out after {} pieces",
+                    game_state.pieces_placed
+                ));
+
+                ui.add_space(10.0);
+
+                if ui.button("Restart").clicked() {
+                    *restart_requested = true;
+                }
+            }
         });
     });
 }
 
-fn get_pent_color(i: u8) -> egui::Color32 {
+fn get_piece_color(i: u8) -> egui::Color32 {
     match i {
         0 => egui::Color32::from_rgb(0, 0, 255),      // bright blue
         1 => egui::Color32::from_rgb(255, 165, 0),    // bright orange
@@ -102,6 +222,13 @@ fn get_pent_color(i: u8) -> egui::Color32 {
         9 => egui::Color32::from_rgb(0, 128, 255),    // bright dark blue
         10 => egui::Color32::from_rgb(255, 0, 0),     // bright red
         11 => egui::Color32::from_rgb(128, 255, 128), // light green
-        _ => egui::Color32::from_rgb(211, 211, 211),  // light gray
+        game::garbage::GARBAGE_PIECE_ID => egui::Color32::GRAY,
+        // larger piece sets, hues spaced by the golden angle stay apart from each other
+        _ => egui::Color32::from(egui::ecolor::Hsva::new(
+            (i as f32 * 0.618_034).fract(),
+            0.75,
+            1.0,
+            1.0,
+        )),
     }
 }